@@ -0,0 +1,45 @@
+use probe_rs::architecture::arm::{
+    component::{enable_tracing, find_component, CycleCounter, Dwt},
+    memory::PeripheralType,
+    DpAddress,
+};
+use probe_rs::{Error, Lister, Permissions};
+
+use std::thread::sleep;
+use std::time::Duration;
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+
+    let lister = Lister::new();
+
+    // Get a list of all available debug probes.
+    let probes = lister.list_all();
+
+    // Use the first probe found.
+    let probe = probes[0].open(&lister)?;
+
+    // Attach to a chip.
+    let mut session = probe.attach("stm32f407", Permissions::default())?;
+
+    // The DWT only counts while trace is enabled.
+    enable_tracing(&mut session.core(0)?)?;
+
+    let components = session.get_arm_components(DpAddress::Default)?;
+    let component = find_component(&components, PeripheralType::Dwt)?;
+    let interface = session.get_arm_interface()?;
+
+    let mut counter = CycleCounter::new(Dwt::new(interface, component))?;
+
+    // Measure the cycles the running core executes in two back-to-back intervals.
+    for interval in 0..2 {
+        sleep(Duration::from_millis(100));
+
+        let cycles = counter.elapsed()?;
+        println!("Interval {interval}: {cycles} cycles");
+
+        counter.restart()?;
+    }
+
+    Ok(())
+}
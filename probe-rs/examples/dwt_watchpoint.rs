@@ -0,0 +1,83 @@
+use probe_rs::architecture::arm::{
+    component::{enable_tracing, find_component, Dwt, WatchKind},
+    memory::PeripheralType,
+    DpAddress,
+};
+use probe_rs::{Lister, Permissions};
+
+use clap::Parser;
+use std::num::ParseIntError;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::Result;
+
+#[derive(clap::Parser)]
+struct Cli {
+    #[clap(long = "chip")]
+    chip: String,
+    #[clap(long = "address", value_parser = parse_hex)]
+    address: u32,
+    #[clap(long = "length", default_value = "4")]
+    length: u32,
+    #[clap(long = "kind", default_value = "write")]
+    kind: WatchKind,
+}
+
+fn parse_hex(src: &str) -> Result<u32, ParseIntError> {
+    u32::from_str_radix(src.trim_start_matches("0x"), 16)
+}
+
+fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    let matches = Cli::parse();
+
+    let lister = Lister::new();
+
+    // Get a list of all available debug probes.
+    let probes = lister.list_all();
+
+    // Use the first probe found.
+    let probe = probes[0].open(&lister)?;
+
+    // Attach to a chip.
+    let mut session = probe.attach(matches.chip, Permissions::default())?;
+
+    // The DWT only operates while trace is enabled.
+    enable_tracing(&mut session.core(0)?)?;
+
+    let components = session.get_arm_components(DpAddress::Default)?;
+    let component = find_component(&components, PeripheralType::Dwt)?;
+
+    let unit = Dwt::new(session.get_arm_interface()?, component).allocate_watchpoint(
+        matches.address,
+        matches.length,
+        matches.kind,
+    )?;
+    println!(
+        "Watching {} accesses to {:#010x} on DWT unit {unit}",
+        matches.kind, matches.address
+    );
+
+    session.core(0)?.run()?;
+
+    // Poll the comparator until it matches. The match also halts the core.
+    loop {
+        let mut dwt = Dwt::new(session.get_arm_interface()?, component);
+        if dwt.watchpoint_matched(unit)? {
+            break;
+        }
+
+        sleep(Duration::from_millis(10));
+    }
+
+    let mut core = session.core(0)?;
+    let pc: u32 = core.read_core_reg(core.program_counter())?;
+    println!("Watchpoint tripped, core stopped at {pc:#010x}");
+
+    drop(core);
+    Dwt::new(session.get_arm_interface()?, component).disable_watchpoint(unit)?;
+
+    Ok(())
+}
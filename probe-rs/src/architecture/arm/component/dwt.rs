@@ -6,15 +6,545 @@
 //! See ARMv7-M architecture reference manual C1.8 for some additional
 //! info about this stuff.
 
-use super::super::memory::romtable::CoresightComponent;
+use super::super::memory::romtable::{
+    Component, CoresightComponent, PeripheralType, RomTableError,
+};
+use super::dwt_regs::{
+    Comp, Cpicnt, Ctrl, Cyccnt, Exccnt, Foldcnt, Function, Lsucnt, Mask, Sleepcnt, SyncTap,
+};
 use super::DebugComponentInterface;
-use crate::architecture::arm::{ArmError, ArmProbeInterface};
-use crate::{memory_mapped_bitfield_register, Error};
+use crate::architecture::arm::{
+    valid_32bit_arm_address, ArmError, ArmProbeInterface, RegisterParseError,
+};
+use crate::{Error, MemoryInterface, MemoryMappedRegister};
+use anyhow::anyhow;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Implements [`MemoryMappedRegister`] for the registers of [`super::dwt_regs`], which can't
+/// depend on it themselves.
+macro_rules! memory_mapped_dwt_registers {
+    ($($register:ident),*) => {
+        $(
+            impl MemoryMappedRegister<u32> for $register {
+                const ADDRESS_OFFSET: u64 = $register::ADDRESS_OFFSET;
+                const NAME: &'static str = $register::NAME;
+            }
+        )*
+    };
+}
+
+memory_mapped_dwt_registers!(
+    Comp, Cpicnt, Ctrl, Cyccnt, Exccnt, Foldcnt, Function, Lsucnt, Mask, Sleepcnt
+);
+
+/// The kind of data access which triggers a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchKind {
+    /// Trigger on data reads.
+    Read,
+    /// Trigger on data writes.
+    Write,
+    /// Trigger on both data reads and writes.
+    ///
+    /// This is what GDB calls an access watchpoint (`awatch`), reported by gdbstub as
+    /// `WatchKind::ReadWrite`. [`WatchKind::ACCESS`] is provided as an alias.
+    ReadWrite,
+}
+
+impl WatchKind {
+    /// Alias for [`WatchKind::ReadWrite`], using the GDB name of the watchpoint kind.
+    pub const ACCESS: WatchKind = WatchKind::ReadWrite;
+}
+
+impl std::fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::ReadWrite => "read/write",
+        })
+    }
+}
+
+impl std::str::FromStr for WatchKind {
+    type Err = String;
+
+    /// Parses the forms written by the [`Display`](std::fmt::Display) implementation,
+    /// as well as the short forms `r`, `w` and `rw` and the GDB name `access`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_ascii_lowercase()[..] {
+            "read" | "r" => Ok(WatchKind::Read),
+            "write" | "w" => Ok(WatchKind::Write),
+            "read/write" | "readwrite" | "rw" | "access" => Ok(WatchKind::ReadWrite),
+            _ => Err(format!(
+                "'{s}' is not a valid watch kind. Choose from [read, write, rw]."
+            )),
+        }
+    }
+}
+
+impl From<WatchKind> for u32 {
+    /// Converts a [`WatchKind`] into the value of the FUNCTION field of a DWT comparator.
+    ///
+    /// | Kind                     | FUNCTION |
+    /// |--------------------------|----------|
+    /// | [`WatchKind::Read`]      | `0b0101` |
+    /// | [`WatchKind::Write`]     | `0b0110` |
+    /// | [`WatchKind::ReadWrite`] | `0b0111` |
+    fn from(kind: WatchKind) -> Self {
+        match kind {
+            WatchKind::Read => 0b0101,
+            WatchKind::Write => 0b0110,
+            WatchKind::ReadWrite => 0b0111,
+        }
+    }
+}
+
+impl TryFrom<u32> for WatchKind {
+    type Error = RegisterParseError;
+
+    /// Converts a value of the FUNCTION field of a DWT comparator back into a [`WatchKind`].
+    ///
+    /// Function codes which don't configure a watchpoint, such as data trace, are rejected.
+    fn try_from(function: u32) -> Result<Self, Self::Error> {
+        match function {
+            0b0101 => Ok(WatchKind::Read),
+            0b0110 => Ok(WatchKind::Write),
+            0b0111 => Ok(WatchKind::ReadWrite),
+            _ => Err(RegisterParseError::new("FUNCTION", function)),
+        }
+    }
+}
+
+/// The data access size a watchpoint comparator operates on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WatchSize {
+    /// Byte accesses.
+    #[default]
+    Byte,
+    /// Halfword accesses.
+    Halfword,
+    /// Word accesses.
+    Word,
+}
+
+impl WatchSize {
+    /// Returns the access size in bytes.
+    fn bytes(self) -> u32 {
+        match self {
+            WatchSize::Byte => 1,
+            WatchSize::Halfword => 2,
+            WatchSize::Word => 4,
+        }
+    }
+}
+
+impl From<WatchSize> for u8 {
+    fn from(size: WatchSize) -> Self {
+        match size {
+            WatchSize::Byte => 0b00,
+            WatchSize::Halfword => 0b01,
+            WatchSize::Word => 0b10,
+        }
+    }
+}
+
+/// The PC sampling setup of a sampling profiler, see [`Dwt::configure_for_profiling`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfilingConfig {
+    /// The frequency of the clock driving the cycle counter in Hz, usually the core clock.
+    pub core_clock: u32,
+    /// The requested number of PC samples per second.
+    pub sample_rate: u32,
+    /// The CYCCNT bit which drives the generation of synchronization packets.
+    pub sync_tap: SyncTap,
+}
+
+/// Selects which packets a DWT comparator emits when used for data tracing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EmitOptions {
+    /// Emit the offset of the accessed address within the traced region.
+    pub emit_range: bool,
+    /// Emit the data value of the access.
+    pub emit_data: bool,
+    /// Emit the program counter of the instruction performing the access.
+    pub emit_pc: bool,
+}
+
+impl EmitOptions {
+    /// Returns the values of the EMITRANGE and FUNCTION fields selecting these packets.
+    ///
+    /// The hardware can't emit the address offset together with the program counter.
+    fn encode(self) -> Result<(bool, u32), ArmError> {
+        match (self.emit_range, self.emit_data, self.emit_pc) {
+            (false, false, true) => Ok((false, 0b0001)),
+            (false, true, false) => Ok((false, 0b0010)),
+            (false, true, true) => Ok((false, 0b0011)),
+            (true, false, false) => Ok((true, 0b0001)),
+            (true, true, false) => Ok((true, 0b0010)),
+            _ => Err(ArmError::Other(anyhow!(
+                "The DWT cannot emit the combination of packets {self:?}"
+            ))),
+        }
+    }
+}
+
+/// The address of the DWT in the system control space of ARMv6-M, ARMv7-M and ARMv8-M cores.
+pub(crate) const CORTEX_M_DWT_BASE: u64 = 0xE000_1000;
+
+/// Returns the number of DWT comparators which are not in use.
+///
+/// The DWT is accessed through `memory`, at the location [`CORTEX_M_DWT_BASE`].
+pub(crate) fn cortex_m_free_comparators(memory: &mut dyn MemoryInterface) -> Result<usize, Error> {
+    let ctrl = Ctrl::from(memory.read_word_32(CORTEX_M_DWT_BASE + Ctrl::ADDRESS_OFFSET)?);
+
+    let mut functions = Vec::new();
+    for unit in 0..ctrl.numcomp() as u64 {
+        let address = CORTEX_M_DWT_BASE + Function::ADDRESS_OFFSET + 16 * unit;
+        functions.push(Function::from(memory.read_word_32(address)?));
+    }
+
+    Ok((0..functions.len())
+        .filter(|&unit| is_unused(&functions, unit))
+        .count())
+}
+
+/// Returns whether comparator `unit` is unused, given the `FUNCTION` registers of all
+/// comparators.
+///
+/// A comparator is unused if its function is disabled, and it doesn't hold the address of an
+/// enabled data value comparator, see [`Dwt::link_comparators`].
+fn is_unused(functions: &[Function], unit: usize) -> bool {
+    functions[unit].function() == 0
+        && !functions.iter().any(|function| {
+            function.function() != 0
+                && function.datavmatch()
+                && (function.datavaddr0() as usize == unit
+                    || function.datavaddr1() as usize == unit)
+        })
+}
+
+/// Reads the configuration of all DWT comparators which are used as watchpoints.
+///
+/// The DWT is accessed through `memory`, at the location [`CORTEX_M_DWT_BASE`].
+pub(crate) fn cortex_m_watchpoints(
+    memory: &mut dyn MemoryInterface,
+) -> Result<Vec<WatchpointConfig>, Error> {
+    let ctrl = Ctrl::from(memory.read_word_32(CORTEX_M_DWT_BASE + Ctrl::ADDRESS_OFFSET)?);
+
+    let mut watchpoints = Vec::new();
+    for unit in 0..ctrl.numcomp() as u64 {
+        let unit_base = CORTEX_M_DWT_BASE + 16 * unit;
+        let function = Function::from(memory.read_word_32(unit_base + Function::ADDRESS_OFFSET)?);
+
+        // Data value comparators do not hold an address.
+        if function.datavmatch() {
+            continue;
+        }
+
+        let Ok(kind) = WatchKind::try_from(function.function()) else {
+            continue;
+        };
+
+        let comp = Comp::from(memory.read_word_32(unit_base + Comp::ADDRESS_OFFSET)?);
+        let mask = Mask::from(memory.read_word_32(unit_base + Mask::ADDRESS_OFFSET)?);
+
+        watchpoints.push(WatchpointConfig {
+            unit: unit as usize,
+            address: comp.comp(),
+            length: 1 << mask.mask(),
+            kind,
+        });
+    }
+
+    Ok(watchpoints)
+}
+
+/// Returns the watchpoint whose comparator matched since the last read of its FUNCTION register.
+///
+/// Reading FUNCTION clears its MATCHED bit, so this has to be called before anything else
+/// accesses the comparators after the core halted. Data value comparators are not reported.
+///
+/// The DWT is accessed through `memory`, at the location [`CORTEX_M_DWT_BASE`].
+pub(crate) fn cortex_m_matched_watchpoint(
+    memory: &mut dyn MemoryInterface,
+) -> Result<Option<WatchpointConfig>, Error> {
+    let ctrl = Ctrl::from(memory.read_word_32(CORTEX_M_DWT_BASE + Ctrl::ADDRESS_OFFSET)?);
+
+    for unit in 0..ctrl.numcomp() as u64 {
+        let unit_base = CORTEX_M_DWT_BASE + 16 * unit;
+        let function = Function::from(memory.read_word_32(unit_base + Function::ADDRESS_OFFSET)?);
+
+        if !function.matched() || function.datavmatch() {
+            continue;
+        }
+
+        let Ok(kind) = WatchKind::try_from(function.function()) else {
+            continue;
+        };
+
+        let comp = Comp::from(memory.read_word_32(unit_base + Comp::ADDRESS_OFFSET)?);
+        let mask = Mask::from(memory.read_word_32(unit_base + Mask::ADDRESS_OFFSET)?);
+
+        return Ok(Some(WatchpointConfig {
+            unit: unit as usize,
+            address: comp.comp(),
+            length: 1 << mask.mask(),
+            kind,
+        }));
+    }
+
+    Ok(None)
+}
+
+/// The configuration of a DWT comparator which is used as a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointConfig {
+    /// The index of the DWT unit.
+    pub unit: usize,
+    /// The start address of the watched region.
+    pub address: u32,
+    /// The length of the watched region in bytes.
+    pub length: u32,
+    /// The kind of access which triggers the watchpoint.
+    pub kind: WatchKind,
+}
+
+/// A builder for watchpoints which use more than the basic comparator features.
+///
+/// The combination of options is validated by [`WatchpointBuilder::apply`], which then
+/// configures the DWT comparators.
+///
+/// ```no_run
+/// # use probe_rs::architecture::arm::component::{Dwt, WatchKind, WatchSize, WatchpointBuilder};
+/// # fn example(dwt: &mut Dwt<'_>) -> Result<(), probe_rs::architecture::arm::ArmError> {
+/// // Halt when 0x1234 is written to the halfword at 0x2000_0010.
+/// WatchpointBuilder::new()
+///     .address(0x2000_0010)
+///     .kind(WatchKind::Write)
+///     .size(WatchSize::Halfword)
+///     .value_match(0x1234)
+///     .apply(dwt, 1)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WatchpointBuilder {
+    address: Option<u32>,
+    length: Option<u32>,
+    kind: Option<WatchKind>,
+    size: WatchSize,
+    value: Option<u32>,
+    emit_range: bool,
+}
+
+impl WatchpointBuilder {
+    /// Creates a builder without any options set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the start address of the watched region. This is required.
+    pub fn address(mut self, address: u32) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Sets the length of the watched region in bytes.
+    ///
+    /// The length has to be a power of two and the address has to be aligned to it. It
+    /// defaults to the access size.
+    pub fn length(mut self, length: u32) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    /// Sets the kind of access which triggers the watchpoint. This is required.
+    pub fn kind(mut self, kind: WatchKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Sets the data access size the comparator operates on. Defaults to
+    /// [`WatchSize::Byte`].
+    pub fn size(mut self, size: WatchSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Only trigger when `value` is accessed, see [`Dwt::enable_data_value_watchpoint`].
+    ///
+    /// This uses a second, unused comparator to hold the address.
+    pub fn value_match(mut self, value: u32) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Sets whether the comparator emits data trace address offset packets on a match.
+    pub fn emit_range(mut self, emit_range: bool) -> Self {
+        self.emit_range = emit_range;
+        self
+    }
+
+    /// Validates the options and configures DWT unit `unit` accordingly.
+    pub fn apply(&self, dwt: &mut Dwt<'_>, unit: usize) -> Result<(), ArmError> {
+        let address = self
+            .address
+            .ok_or_else(|| ArmError::Other(anyhow!("The watchpoint address is not set")))?;
+        let kind = self
+            .kind
+            .ok_or_else(|| ArmError::Other(anyhow!("The watchpoint kind is not set")))?;
+        let size = self.size.bytes();
+        let length = self.length.unwrap_or(size);
+
+        if let Some(value) = self.value {
+            if self.emit_range {
+                return Err(ArmError::Other(anyhow!(
+                    "Data value matching cannot be combined with emitting the address range"
+                )));
+            }
+            if length != size {
+                return Err(ArmError::Other(anyhow!(
+                    "Data value matching compares a single access, but the length {length} differs from the access size {size}"
+                )));
+            }
+
+            return dwt.enable_data_value_watchpoint(unit, address, value, size, kind);
+        }
+
+        check_region(address, length)?;
+
+        let mut comparator = dwt.comparator(unit)?;
+        comparator.set_address(address);
+        comparator.set_mask_bits(length.trailing_zeros());
+        comparator.function.set_datavsize(self.size.into());
+        comparator.function.set_emitrange(self.emit_range);
+        comparator.function.set_datavmatch(false);
+        comparator.function.set_cycmatch(false);
+        comparator.set_function(kind.into());
+
+        comparator.commit()
+    }
+}
+
+/// The register values of a DWT component, see [`Dwt::snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DwtState {
+    /// The value of the `CTRL` register.
+    pub ctrl: u32,
+    /// The registers of each comparator.
+    pub comparators: Vec<ComparatorState>,
+}
+
+/// The register values of a single DWT comparator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComparatorState {
+    /// The value of the `COMP` register.
+    pub comp: u32,
+    /// The value of the `MASK` register.
+    pub mask: u32,
+    /// The value of the `FUNCTION` register.
+    pub function: u32,
+}
+
+/// A watchpoint which triggers on the `n`-th access, see [`Dwt::enable_nth_access_watchpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NthAccessWatchpoint {
+    /// The index of the DWT unit.
+    pub unit: usize,
+    remaining: u32,
+}
+
+impl NthAccessWatchpoint {
+    /// Counts a hit of the watchpoint, which halted the core.
+    ///
+    /// Returns `true` on the `n`-th access. Before, the core has to be resumed to continue
+    /// counting. The comparator stays enabled, so later accesses halt the core again.
+    pub fn hit(&mut self) -> bool {
+        self.remaining = self.remaining.saturating_sub(1);
+        self.remaining == 0
+    }
+
+    /// Returns the number of accesses left until the watchpoint triggers.
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+}
+
+/// The features implemented by a DWT component, as reported by its `CTRL` register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DwtCapabilities {
+    /// The number of comparators.
+    pub numcomp: u8,
+    /// Whether trace sampling and exception tracing is supported.
+    pub trace_sampling: bool,
+    /// Whether external match signals (`CMPMATCH`) are supported.
+    pub compare_match: bool,
+    /// Whether the cycle counter is supported.
+    pub cyccnt: bool,
+    /// Whether the profiling counters are supported.
+    pub perf_counter: bool,
+}
+
+/// The features of a single DWT comparator, see [`Dwt::unit_capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitCapabilities {
+    /// Whether the comparator supports data value matching.
+    pub value_match: bool,
+    /// Whether the comparator can match the cycle counter.
+    pub cycle_match: bool,
+    /// The largest number of low address bits the comparator can ignore.
+    pub max_mask_bits: u32,
+}
+
+/// Formats the capabilities like the log output of [`Dwt::info`], one feature per line.
+impl std::fmt::Display for DwtCapabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "DWT info:")?;
+        writeln!(f, "  number of comparators available: {}", self.numcomp)?;
+        writeln!(f, "  trace sampling support: {}", self.trace_sampling)?;
+        writeln!(f, "  compare match support: {}", self.compare_match)?;
+        writeln!(f, "  cyccnt support: {}", self.cyccnt)?;
+        write!(f, "  performance counter support: {}", self.perf_counter)
+    }
+}
+
+/// The order in which [`Dwt`] searches for an unused comparator.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationStrategy {
+    /// Use the unused comparator with the lowest index.
+    #[default]
+    LowestFirst,
+    /// Use the unused comparator with the highest index, which keeps the low comparators free
+    /// for firmware using them.
+    HighestFirst,
+}
+
+/// The number of register transfers performed by a [`Dwt`], see [`Dwt::transfer_stats`].
+#[cfg(feature = "dwt-stats")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DwtTransferStats {
+    /// The number of register reads. A block read of several registers counts once.
+    pub reads: usize,
+    /// The number of register writes.
+    pub writes: usize,
+}
 
 /// A struct representing a DWT unit on target.
+///
+/// The configuration is kept in the registers of the DWT only, so a `Dwt` can be dropped
+/// and created again at any time to release the interface. While it exists, the interface
+/// can be used for other accesses through [`Dwt::interface`].
 pub struct Dwt<'a> {
     component: &'a CoresightComponent,
     interface: &'a mut dyn ArmProbeInterface,
+    access_time_limit: Option<Duration>,
+    strategy: AllocationStrategy,
+    reservations: CyccntReservations,
+    #[cfg(feature = "dwt-stats")]
+    stats: DwtTransferStats,
 }
 
 impl<'a> Dwt<'a> {
@@ -26,165 +556,2717 @@ impl<'a> Dwt<'a> {
         Dwt {
             interface,
             component,
+            access_time_limit: None,
+            strategy: AllocationStrategy::default(),
+            reservations: CyccntReservations::default(),
+            #[cfg(feature = "dwt-stats")]
+            stats: DwtTransferStats::default(),
+        }
+    }
+
+    /// Creates a new DWT component representation which fails register accesses taking
+    /// longer than `limit` with [`ArmError::Timeout`].
+    ///
+    /// This is not a deadline: the probe transfer is not aborted, so a hanging access still
+    /// blocks until the probe gives up. The duration is only checked once the access has
+    /// returned, and the remaining accesses of an operation are then skipped. This makes
+    /// operations touching several registers fail fast on a slow or unreliable link.
+    pub fn with_access_time_limit(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+        limit: Duration,
+    ) -> Self {
+        Dwt {
+            interface,
+            component,
+            access_time_limit: Some(limit),
+            strategy: AllocationStrategy::default(),
+            reservations: CyccntReservations::default(),
+            #[cfg(feature = "dwt-stats")]
+            stats: DwtTransferStats::default(),
+        }
+    }
+
+    /// Sets the order in which unused comparators are searched when a watchpoint is
+    /// allocated.
+    pub fn set_allocation_strategy(&mut self, strategy: AllocationStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// Sets the reservations of the cycle counter this `Dwt` respects, see [`Dwt::reserve_cyccnt`].
+    ///
+    /// A new `Dwt` starts with its own, empty set of reservations.
+    pub fn set_cyccnt_reservations(&mut self, reservations: CyccntReservations) {
+        self.reservations = reservations;
+    }
+
+    /// Returns the reservations of the cycle counter this `Dwt` respects.
+    pub fn cyccnt_reservations(&self) -> CyccntReservations {
+        self.reservations.clone()
+    }
+
+    /// Returns the indices of all comparators, in the order of the allocation strategy.
+    fn allocation_order(&mut self) -> Result<Vec<usize>, ArmError> {
+        let units = 0..self.comparator_count()? as usize;
+        Ok(match self.strategy {
+            AllocationStrategy::LowestFirst => units.collect(),
+            AllocationStrategy::HighestFirst => units.rev().collect(),
+        })
+    }
+
+    /// Returns the interface used to access the DWT.
+    ///
+    /// This allows other accesses in between configuring the DWT, for example to read the
+    /// memory of the target to decide where to place a watchpoint.
+    pub fn interface(&mut self) -> &mut dyn ArmProbeInterface {
+        self.interface
+    }
+
+    /// Returns the number of register transfers performed since this `Dwt` was created.
+    ///
+    /// Accesses through [`Dwt::interface`] and the identification registers read by
+    /// [`Dwt::is_present`] are not counted.
+    #[cfg(feature = "dwt-stats")]
+    pub fn transfer_stats(&self) -> DwtTransferStats {
+        self.stats
+    }
+
+    fn count_read(&mut self) {
+        #[cfg(feature = "dwt-stats")]
+        {
+            self.stats.reads += 1;
+        }
+    }
+
+    fn count_write(&mut self) {
+        #[cfg(feature = "dwt-stats")]
+        {
+            self.stats.writes += 1;
+        }
+    }
+
+    fn load<R: DebugComponentInterface>(&mut self) -> Result<R, ArmError> {
+        self.count_read();
+        reject_slow_access(self.access_time_limit, || {
+            R::load(self.component, self.interface)
+        })
+    }
+
+    fn load_unit<R: DebugComponentInterface>(&mut self, unit: usize) -> Result<R, ArmError> {
+        self.count_read();
+        reject_slow_access(self.access_time_limit, || {
+            R::load_unit(self.component, self.interface, unit)
+        })
+    }
+
+    fn store<R: DebugComponentInterface>(&mut self, register: R) -> Result<(), ArmError> {
+        self.count_write();
+        reject_slow_access(self.access_time_limit, || {
+            register.store(self.component, self.interface)
+        })
+    }
+
+    fn store_unit<R: DebugComponentInterface>(
+        &mut self,
+        register: R,
+        unit: usize,
+    ) -> Result<(), ArmError> {
+        self.count_write();
+        reject_slow_access(self.access_time_limit, || {
+            register.store_unit(self.component, self.interface, unit)
+        })
+    }
+
+    /// Reads the DWT register at `offset` bytes from the base address of the DWT component.
+    ///
+    /// This allows accessing registers which are not modelled by this type, such as vendor
+    /// extensions.
+    pub fn read_raw_register(&mut self, offset: u32) -> Result<u32, ArmError> {
+        self.count_read();
+        reject_slow_access(self.access_time_limit, || {
+            self.component.read_reg(self.interface, offset)
+        })
+    }
+
+    /// Writes `value` to the DWT register at `offset` bytes from the base address of the DWT
+    /// component.
+    ///
+    /// See [`Dwt::read_raw_register`].
+    pub fn write_raw_register(&mut self, offset: u32, value: u32) -> Result<(), ArmError> {
+        self.count_write();
+        reject_slow_access(self.access_time_limit, || {
+            self.component.write_reg(self.interface, offset, value)
+        })
+    }
+
+    /// Returns whether the component is a DWT.
+    ///
+    /// The component and peripheral ID registers are read again from the target, so
+    /// callers can skip DWT features on parts which don't implement one, such as some
+    /// Cortex-M0 devices. An unrecognized component returns `false`, only failed memory
+    /// accesses are reported as errors.
+    pub fn is_present(&mut self) -> Result<bool, ArmError> {
+        let address = self.component.component.id().component_address();
+        let mut memory = self.interface.memory_interface(self.component.ap)?;
+
+        match Component::try_parse(&mut *memory, address) {
+            Ok(component) => Ok(component
+                .id()
+                .peripheral_id()
+                .is_of_type(PeripheralType::Dwt)),
+            Err(RomTableError::Memory(error)) => Err(*error),
+            Err(_) => Ok(false),
         }
     }
 
     /// Logs some info about the DWT component.
     pub fn info(&mut self) -> Result<(), Error> {
-        let ctrl = Ctrl::load(self.component, self.interface)?;
+        let capabilities = self.capabilities()?;
 
         tracing::info!("DWT info:");
-        tracing::info!("  number of comparators available: {}", ctrl.numcomp());
-        tracing::info!("  trace sampling support: {}", !ctrl.notrcpkt());
-        tracing::info!("  compare match support: {}", !ctrl.noexttrig());
-        tracing::info!("  cyccnt support: {}", !ctrl.nocyccnt());
-        tracing::info!("  performance counter support: {}", !ctrl.noprfcnt());
+        tracing::info!(
+            "  number of comparators available: {}",
+            capabilities.numcomp
+        );
+        tracing::info!("  trace sampling support: {}", capabilities.trace_sampling);
+        tracing::info!("  compare match support: {}", capabilities.compare_match);
+        tracing::info!("  cyccnt support: {}", capabilities.cyccnt);
+        tracing::info!(
+            "  performance counter support: {}",
+            capabilities.perf_counter
+        );
 
         Ok(())
     }
 
+    /// Reads which features are implemented by the DWT component.
+    pub fn capabilities(&mut self) -> Result<DwtCapabilities, ArmError> {
+        let ctrl = self.load::<Ctrl>()?;
+
+        Ok(DwtCapabilities {
+            numcomp: ctrl.numcomp(),
+            trace_sampling: !ctrl.notrcpkt(),
+            compare_match: !ctrl.noexttrig(),
+            cyccnt: !ctrl.nocyccnt(),
+            perf_counter: !ctrl.noprfcnt(),
+        })
+    }
+
+    /// Returns the number of comparators implemented by the DWT.
+    pub fn comparator_count(&mut self) -> Result<u8, ArmError> {
+        let ctrl = self.load::<Ctrl>()?;
+        Ok(ctrl.numcomp())
+    }
+
+    /// Returns the number of comparators which are not in use.
+    ///
+    /// A comparator is considered unused if its function is disabled, and it doesn't hold the
+    /// address of an enabled data value watchpoint.
+    pub fn free_comparator_count(&mut self) -> Result<usize, ArmError> {
+        Ok(self.free_units()?.len())
+    }
+
     /// Enables the DWT component.
+    ///
+    /// This starts the cycle counter, which also drives the synchronization packets. Only the
+    /// features implemented by the DWT are enabled, a warning is logged for the others. If
+    /// the DWT is already enabled, `CTRL` is not written again.
     pub fn enable(&mut self) -> Result<(), ArmError> {
-        let mut ctrl = Ctrl::load(self.component, self.interface)?;
-        ctrl.set_synctap(0x01);
+        let mut ctrl = self.load::<Ctrl>()?;
+
+        if ctrl.nocyccnt() {
+            tracing::warn!("The DWT does not implement a cycle counter, which is not enabled");
+            return Ok(());
+        }
+
+        if ctrl.cyccntena() && ctrl.sync_tap() == SyncTap::Cyccnt24 {
+            return Ok(());
+        }
+
+        ctrl.set_sync_tap(SyncTap::Cyccnt24);
         ctrl.set_cyccntena(true);
-        ctrl.store(self.component, self.interface)
+        self.store(ctrl)
+    }
+
+    /// Reads the `CTRL` register, applies `f` to it and writes it back.
+    ///
+    /// This allows changing several settings with a single read-modify-write cycle,
+    /// where calling the individual methods accesses the register once per setting.
+    pub fn modify_ctrl(&mut self, f: impl FnOnce(&mut Ctrl)) -> Result<(), ArmError> {
+        let mut ctrl = self.load::<Ctrl>()?;
+        f(&mut ctrl);
+        self.store(ctrl)
+    }
+
+    /// Returns an error if the DWT does not implement comparator `unit`.
+    ///
+    /// Accessing the registers of an unimplemented comparator would access
+    /// unrelated registers instead.
+    fn check_unit(&mut self, unit: usize) -> Result<(), ArmError> {
+        if unit >= self.comparator_count()? as usize {
+            return Err(ArmError::OutOfBounds);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the registers of comparator `unit`.
+    ///
+    /// The comparator is only reconfigured once [`Comparator::commit`] is called.
+    pub fn comparator(&mut self, unit: usize) -> Result<Comparator<'_>, ArmError> {
+        self.check_unit(unit)?;
+
+        Ok(Comparator {
+            comp: self.load_unit::<Comp>(unit)?,
+            mask: self.load_unit::<Mask>(unit)?,
+            function: self.load_unit::<Function>(unit)?,
+            component: self.component,
+            interface: self.interface,
+            access_time_limit: self.access_time_limit,
+            #[cfg(feature = "dwt-stats")]
+            stats: &mut self.stats,
+            unit,
+        })
+    }
+
+    /// Reads the raw value of the `FUNCTION` register of comparator `unit`.
+    ///
+    /// This is a low-level diagnostics aid, [`Dwt::comparator`] provides the decoded fields.
+    /// Reading the register clears its `MATCHED` bit.
+    pub fn read_function(&mut self, unit: usize) -> Result<u32, ArmError> {
+        self.check_unit(unit)?;
+        Ok(self.load_unit::<Function>(unit)?.into())
+    }
+
+    /// Reads the raw value of the `COMP` register of comparator `unit`.
+    ///
+    /// See [`Dwt::read_function`].
+    pub fn read_comp(&mut self, unit: usize) -> Result<u32, ArmError> {
+        self.check_unit(unit)?;
+        Ok(self.load_unit::<Comp>(unit)?.into())
+    }
+
+    /// Reads the raw value of the `MASK` register of comparator `unit`.
+    ///
+    /// See [`Dwt::read_function`].
+    pub fn read_mask(&mut self, unit: usize) -> Result<u32, ArmError> {
+        self.check_unit(unit)?;
+        Ok(self.load_unit::<Mask>(unit)?.into())
     }
 
     /// Enables data tracing on a specific address in memory on a specific DWT unit.
+    ///
+    /// Both the data value and the PC of each access are emitted, see
+    /// [`Dwt::enable_data_trace_with_options`].
+    #[tracing::instrument(skip(self))]
     pub fn enable_data_trace(&mut self, unit: usize, address: u32) -> Result<(), ArmError> {
-        let mut comp = Comp::load_unit(self.component, self.interface, unit)?;
-        comp.set_comp(address);
-        comp.store_unit(self.component, self.interface, unit)?;
+        self.enable_data_trace_range(unit, address, 1)
+    }
+
+    /// Enables data tracing on DWT unit `unit` for the memory region starting
+    /// at `address` which is `length` bytes long.
+    ///
+    /// The same restrictions as for [`Dwt::enable_watchpoint`] apply: `length`
+    /// has to be a power of two and `address` has to be aligned to it.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_data_trace_range(
+        &mut self,
+        unit: usize,
+        address: u32,
+        length: u32,
+    ) -> Result<(), ArmError> {
+        let options = EmitOptions {
+            emit_range: false,
+            emit_data: true,
+            emit_pc: true,
+        };
+        self.configure_data_trace(unit, address, length, options)
+    }
+
+    /// Enables data tracing on a specific address in memory on a specific DWT unit,
+    /// emitting the packets selected by `options`.
+    ///
+    /// The options select the FUNCTION field of the comparator, which is
+    /// interpreted depending on the EMITRANGE bit:
+    ///
+    /// | FUNCTION | EMITRANGE = 0                | EMITRANGE = 1                   |
+    /// |----------|------------------------------|---------------------------------|
+    /// | `0b0001` | PC of the access             | Address offset                  |
+    /// | `0b0010` | Data value                   | Data value and address offset   |
+    /// | `0b0011` | PC and data value            | Not used                        |
+    ///
+    /// Emitting the PC tells which instruction accessed the traced location,
+    /// the data value tells what was read or written. Combinations not listed
+    /// in the table are rejected.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_data_trace_with_options(
+        &mut self,
+        unit: usize,
+        address: u32,
+        options: EmitOptions,
+    ) -> Result<(), ArmError> {
+        self.configure_data_trace(unit, address, 1, options)
+    }
 
-        let mut mask = Mask::load_unit(self.component, self.interface, unit)?;
-        mask.set_mask(0x0);
-        mask.store_unit(self.component, self.interface, unit)?;
+    fn configure_data_trace(
+        &mut self,
+        unit: usize,
+        address: u32,
+        length: u32,
+        options: EmitOptions,
+    ) -> Result<(), ArmError> {
+        check_region(address, length)?;
+        let (emit_range, function_code) = options.encode()?;
 
-        let mut function = Function::load_unit(self.component, self.interface, unit)?;
-        function.set_datavsize(0x10);
-        function.set_emitrange(false);
-        function.set_datavmatch(false);
-        function.set_cycmatch(false);
-        function.set_function(0b11);
+        let mut comparator = self.comparator(unit)?;
+        comparator.set_address(address);
+        comparator.set_mask_bits(length.trailing_zeros());
+        comparator.function.set_datavsize(WatchSize::Word.into());
+        comparator.function.set_emitrange(emit_range);
+        comparator.function.set_datavmatch(false);
+        comparator.function.set_cycmatch(false);
+        comparator.set_function(function_code);
 
-        function.store_unit(self.component, self.interface, unit)
+        comparator.commit()
     }
 
     /// Disables data tracing on the given unit.
+    #[tracing::instrument(skip(self))]
     pub fn disable_data_trace(&mut self, unit: usize) -> Result<(), ArmError> {
-        let mut function = Function::load_unit(self.component, self.interface, unit)?;
+        self.check_unit(unit)?;
+
+        let mut function = self.load_unit::<Function>(unit)?;
         function.set_function(0x0);
-        function.store_unit(self.component, self.interface, unit)
+        self.store_unit(function, unit)
     }
 
-    /// Enable exception tracing.
-    pub fn enable_exception_trace(&mut self) -> Result<(), ArmError> {
-        let mut ctrl = Ctrl::load(self.component, self.interface)?;
-        ctrl.set_exctrcena(true);
-        ctrl.store(self.component, self.interface)
+    /// Configures DWT unit `unit` as a watchpoint on the memory region
+    /// starting at `address` which is `length` bytes long.
+    ///
+    /// `length` has to be a power of two and `address` has to be aligned to it,
+    /// as the comparator matches by ignoring the low address bits. The comparator can't ignore
+    /// more bits than its `MASK` register implements, which limits `length`. A `length` of zero
+    /// watches the single byte at `address`.
+    ///
+    /// The registers are written one after another, so a running core can trigger spurious
+    /// matches. Halting the core while configuring the comparator is the responsibility of the
+    /// caller, see [`Session::with_core_halted`](crate::Session::with_core_halted).
+    #[tracing::instrument(skip(self))]
+    pub fn enable_watchpoint(
+        &mut self,
+        unit: usize,
+        address: u32,
+        length: u32,
+        kind: WatchKind,
+    ) -> Result<(), ArmError> {
+        self.enable_sized_watchpoint(unit, address, length, kind, WatchSize::Byte)
     }
 
-    /// Disable exception tracing.
-    pub fn disable_exception_trace(&mut self) -> Result<(), ArmError> {
-        let mut ctrl = Ctrl::load(self.component, self.interface)?;
-        ctrl.set_exctrcena(false);
-        ctrl.store(self.component, self.interface)
+    /// Configures DWT unit `unit` as a watchpoint like [`Dwt::enable_watchpoint`],
+    /// taking the address as a `u64`.
+    ///
+    /// The comparators match 32 bit addresses, so an address which doesn't fit is rejected
+    /// with [`ArmError::AddressOutOf32BitAddressSpace`] instead of being truncated.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_watchpoint_u64(
+        &mut self,
+        unit: usize,
+        address: u64,
+        length: u32,
+        kind: WatchKind,
+    ) -> Result<(), ArmError> {
+        let address = valid_32bit_arm_address(address)?;
+        self.enable_watchpoint(unit, address, length, kind)
     }
 
-    /// Enable PC sample trace output
-    pub fn enable_pc_sampling(&mut self) -> Result<(), ArmError> {
-        let mut ctrl = Ctrl::load(self.component, self.interface)?;
-        ctrl.set_pcsamplena(true);
-        ctrl.set_cyctap(true);
-        ctrl.set_postpreset(0x3);
-        ctrl.store(self.component, self.interface)
-    }
-}
-
-memory_mapped_bitfield_register! {
-    pub struct Ctrl(u32);
-    0x00, "DWT/CTRL",
-    impl From;
-    pub u8, numcomp, _: 31, 28;
-    pub notrcpkt, _: 27;
-    pub noexttrig, _: 26;
-    pub nocyccnt, _: 25;
-    pub noprfcnt, _: 24;
-    pub cycevtena, set_cycevtena: 22;
-    pub foldevtena, set_foldevtena: 21;
-    pub lsuevtena, set_lsuevtena: 20;
-    pub sleepevtena, set_sleepevtena: 19;
-    pub excevtena, set_excevtena: 18;
-    pub cpievtena, set_cpievtena: 17;
-    pub exctrcena, set_exctrcena: 16;
-    pub pcsamplena, set_pcsamplena: 12;
-    /// 00 Disabled. No Synchronization packets.
-    /// 01 Synchronization counter tap at CYCCNT[24].
-    /// 10 Synchronization counter tap at CYCCNT[26].
-    /// 11 Synchronization counter tap at CYCCNT[28].
-    pub u8, synctap, set_synctap: 11, 10;
-    pub cyctap, set_cyctap: 9;
-    pub u8, postinit, set_postinit: 8, 5;
-    pub postpreset, set_postpreset: 4, 1;
-    pub cyccntena, set_cyccntena: 0;
+    /// Configures DWT unit `unit` as a watchpoint like [`Dwt::enable_watchpoint`],
+    /// using `size` as the data access size of the comparator.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_sized_watchpoint(
+        &mut self,
+        unit: usize,
+        address: u32,
+        length: u32,
+        kind: WatchKind,
+        size: WatchSize,
+    ) -> Result<(), ArmError> {
+        let length = length.max(1);
+        check_region(address, length)?;
 
-}
+        // A single byte is matched without ignoring any address bits.
+        if length > 1 {
+            self.check_unit(unit)?;
 
-impl DebugComponentInterface for Ctrl {}
+            let max_mask_size = self.max_mask_size(unit)?;
+            if length.trailing_zeros() > max_mask_size {
+                return Err(ArmError::Other(anyhow!(
+                    "DWT unit {unit} can watch at most {} bytes, not {length}",
+                    1u64 << max_mask_size
+                )));
+            }
+        }
 
-memory_mapped_bitfield_register! {
-    pub struct Cyccnt(u32);
-    0x04, "DWT/CYCCNT",
-    impl From;
-}
+        let mut comparator = self.comparator(unit)?;
+        comparator.set_address(address);
+        comparator.set_mask_bits(length.trailing_zeros());
+        comparator.function.set_datavsize(size.into());
+        comparator.function.set_emitrange(false);
+        comparator.function.set_datavmatch(false);
+        comparator.function.set_cycmatch(false);
+        comparator.set_function(kind.into());
 
-memory_mapped_bitfield_register! {
-    pub struct Cpicnt(u32);
-    0x08, "DWT/CPICNT",
-    impl From;
-}
+        comparator.commit()
+    }
 
-memory_mapped_bitfield_register! {
-    pub struct Exccnt(u32);
-    0x0C, "DWT/EXCCNT",
-    impl From;
-}
+    /// Configures DWT unit `unit` as a watchpoint covering at least `length` bytes starting at
+    /// `address`, rounding the region if needed, and returns the watched length.
+    ///
+    /// In contrast to [`Dwt::enable_watchpoint`], which rejects regions the comparator can't
+    /// match exactly, the length is rounded up to the smallest power of two whose aligned block
+    /// contains the whole region. The watched block starts at `address` aligned down to the
+    /// returned length, and a warning is logged if it is larger than requested.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_rounded_watchpoint(
+        &mut self,
+        unit: usize,
+        address: u32,
+        length: u32,
+        kind: WatchKind,
+    ) -> Result<u32, ArmError> {
+        self.check_unit(unit)?;
 
-memory_mapped_bitfield_register! {
-    pub struct Comp(u32);
-    0x20, "DWT/COMP",
-    impl From;
-    pub u32, comp, set_comp: 31, 0;
-}
+        let mut ignore_bits = length.max(1).next_power_of_two().trailing_zeros();
+        let last = address as u64 + length.max(1) as u64 - 1;
+        while (address as u64) >> ignore_bits != last >> ignore_bits {
+            ignore_bits += 1;
+        }
 
-impl DebugComponentInterface for Comp {}
+        let watched = 1u64 << ignore_bits;
+        if watched != length as u64 {
+            tracing::warn!(
+                "Watching {watched} bytes at {:#010x} to cover {length} bytes at {address:#010x}",
+                address as u64 & !(watched - 1)
+            );
+        }
 
-memory_mapped_bitfield_register! {
-    pub struct Mask(u32);
-    0x24, "DWT/MASK",
-    impl From;
-    pub u32, mask, set_mask: 4, 0;
-}
+        self.enable_masked_watchpoint(unit, address, ignore_bits, kind)?;
 
-impl DebugComponentInterface for Mask {}
+        Ok(watched as u32)
+    }
 
-memory_mapped_bitfield_register! {
-    pub struct Function(u32);
-    0x28, "DWT/FUNCTION",
-    impl From;
-    pub matched, _: 24;
-    pub u8, datavaddr1, set_datavaddr1: 19, 16;
-    pub u8, datavaddr0, set_datavaddr0: 15, 12;
-    /// 00 Byte.
-    /// 01 Halfword.
-    /// 10 Word.
-    pub u8, datavsize, set_datavsize: 11, 10;
-    pub lnk1ena, _: 9;
-    pub datavmatch, set_datavmatch: 8;
-    pub cycmatch, set_cycmatch: 7;
-    pub emitrange, set_emitrange: 5;
-    pub function, set_function: 3, 0;
-}
+    /// Configures DWT unit `unit` as a watchpoint which ignores the lowest `ignore_bits` bits
+    /// of the address.
+    ///
+    /// This watches the aligned block of `1 << ignore_bits` bytes containing `address`, with
+    /// `MASK` set directly instead of being derived from a length like in
+    /// [`Dwt::enable_watchpoint`]. The ignored bits of `address` are cleared. `ignore_bits` can't
+    /// exceed the largest mask implemented by the comparator.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_masked_watchpoint(
+        &mut self,
+        unit: usize,
+        address: u32,
+        ignore_bits: u32,
+        kind: WatchKind,
+    ) -> Result<(), ArmError> {
+        self.check_unit(unit)?;
 
-impl DebugComponentInterface for Function {}
+        let max_mask_size = self.max_mask_size(unit)?;
+        if ignore_bits > max_mask_size {
+            return Err(ArmError::Other(anyhow!(
+                "DWT unit {unit} can ignore at most {max_mask_size} address bits, not {ignore_bits}"
+            )));
+        }
+
+        let mut comparator = self.comparator(unit)?;
+        comparator.set_address(address & !((1 << ignore_bits) - 1));
+        comparator.set_mask_bits(ignore_bits);
+        comparator.function.set_datavsize(WatchSize::Byte.into());
+        comparator.function.set_emitrange(false);
+        comparator.function.set_datavmatch(false);
+        comparator.function.set_cycmatch(false);
+        comparator.set_function(kind.into());
+
+        comparator.commit()
+    }
+
+    /// Disables the watchpoint on the given unit.
+    #[tracing::instrument(skip(self))]
+    pub fn disable_watchpoint(&mut self, unit: usize) -> Result<(), ArmError> {
+        self.check_unit(unit)?;
+
+        let mut function = self.load_unit::<Function>(unit)?;
+        function.set_function(0x0);
+        self.store_unit(function, unit)
+    }
+
+    /// Returns the index of the unit with an enabled watchpoint on `address`, if any.
+    ///
+    /// Data value comparators are not considered, as they don't hold an address.
+    pub fn find_watchpoint(&mut self, address: u32) -> Result<Option<usize>, ArmError> {
+        for unit in 0..self.comparator_count()? as usize {
+            let function = self.load_unit::<Function>(unit)?;
+            if function.function() == 0 || function.datavmatch() {
+                continue;
+            }
+
+            let comp = self.load_unit::<Comp>(unit)?;
+            if comp.comp() == address {
+                return Ok(Some(unit));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns whether the comparator of DWT unit `unit` matched since the last call.
+    ///
+    /// # Side effects
+    ///
+    /// Reading the `MATCHED` bit clears it in hardware, so each match is only
+    /// reported once. Any other read of the `FUNCTION` register, for example a
+    /// reconfiguration of the unit, clears it as well.
+    pub fn watchpoint_matched(&mut self, unit: usize) -> Result<bool, ArmError> {
+        self.check_unit(unit)?;
+
+        let function = self.load_unit::<Function>(unit)?;
+        Ok(function.matched())
+    }
+
+    /// Clears the `MATCHED` bit of DWT unit `unit`, so the next hit of its watchpoint can be
+    /// detected, and returns whether it was set.
+    ///
+    /// This is a plain read of the `FUNCTION` register, like [`Dwt::watchpoint_matched`], as
+    /// reading clears the bit. The configuration of the comparator is not changed. If the
+    /// comparator has been disabled in the meantime, an error is returned.
+    #[tracing::instrument(skip(self))]
+    pub fn rearm_watchpoint(&mut self, unit: usize) -> Result<bool, ArmError> {
+        self.check_unit(unit)?;
+
+        let function = self.load_unit::<Function>(unit)?;
+        if function.function() == 0 {
+            return Err(ArmError::Other(anyhow!(
+                "DWT unit {unit} is disabled, it can't be re-armed"
+            )));
+        }
+
+        Ok(function.matched())
+    }
+
+    /// Returns a bitmask of the comparators which matched, bit `n` being set if comparator
+    /// `n` matched.
+    ///
+    /// The registers of all comparators are read with a single block read instead of one
+    /// access per comparator. As for [`Dwt::watchpoint_matched`], reading clears the
+    /// `MATCHED` bit of every comparator, so each match is reported once.
+    pub fn matched_mask(&mut self) -> Result<u32, ArmError> {
+        let numcomp = self.comparator_count()? as usize;
+        let address = self.component.component.id().component_address() + Comp::ADDRESS_OFFSET;
+
+        // COMP, MASK, FUNCTION and a reserved word per comparator.
+        let mut registers = vec![0; 4 * numcomp];
+        self.count_read();
+        reject_slow_access(self.access_time_limit, || {
+            let mut memory = self.interface.memory_interface(self.component.ap)?;
+            memory.read_32(address, &mut registers)
+        })?;
+
+        let mask = registers
+            .chunks(4)
+            .enumerate()
+            .filter(|(_, registers)| Function::from(registers[2]).matched())
+            .fold(0, |mask, (unit, _)| mask | 1 << unit);
+
+        Ok(mask)
+    }
+
+    /// Configures a watchpoint on the first unused DWT unit and returns its index.
+    ///
+    /// A unit is considered unused if its function is disabled. If all units are in use,
+    /// [`ArmError::NoFreeComparator`] is returned. See [`Dwt::enable_watchpoint`] for the
+    /// requirements on `address` and `length`.
+    #[tracing::instrument(skip(self))]
+    pub fn allocate_watchpoint(
+        &mut self,
+        address: u32,
+        length: u32,
+        kind: WatchKind,
+    ) -> Result<usize, ArmError> {
+        let unit = self.first_free_unit()?;
+        self.enable_watchpoint(unit, address, length, kind)?;
+        Ok(unit)
+    }
+
+    /// Configures a watchpoint on the first unused DWT unit covering the peripheral register
+    /// window of `size` bytes at `base`, and returns the index of the unit.
+    ///
+    /// A comparator covers the window by ignoring the low address bits, see
+    /// [`Dwt::enable_masked_watchpoint`]. The window therefore has to be a power of two in size,
+    /// with `base` aligned to it, which is the case for the register blocks of most peripherals.
+    /// Windows larger than the largest mask implemented by the comparator are rejected.
+    #[tracing::instrument(skip(self))]
+    pub fn watch_peripheral(
+        &mut self,
+        base: u32,
+        size: u32,
+        kind: WatchKind,
+    ) -> Result<usize, ArmError> {
+        if !size.is_power_of_two() || base % size != 0 {
+            return Err(ArmError::Other(anyhow!(
+                "The peripheral window of {size} bytes at {base:#010x} can't be watched by a single comparator, its size has to be a power of two and its base aligned to it"
+            )));
+        }
+
+        let unit = self.first_free_unit()?;
+        self.enable_masked_watchpoint(unit, base, size.trailing_zeros(), kind)?;
+        Ok(unit)
+    }
+
+    /// Configures DWT unit `unit` to match instruction fetches from `address`.
+    ///
+    /// This acts like a hardware breakpoint, except that the match generates a
+    /// watchpoint debug event. Unlike a breakpoint of the FPB, this event may
+    /// only halt the core after the instruction at `address` has executed.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_instruction_watchpoint(
+        &mut self,
+        unit: usize,
+        address: u32,
+    ) -> Result<(), ArmError> {
+        let mut comparator = self.comparator(unit)?;
+        comparator.set_address(address);
+        comparator.set_mask_bits(0x0);
+        comparator.function.set_emitrange(false);
+        comparator.function.set_datavmatch(false);
+        comparator.function.set_cycmatch(false);
+        // PC match
+        comparator.set_function(0b0100);
+
+        comparator.commit()
+    }
+
+    /// Configures an instruction watchpoint on the first unused DWT unit and
+    /// returns its index, see [`Dwt::enable_instruction_watchpoint`].
+    #[tracing::instrument(skip(self))]
+    pub fn allocate_instruction_watchpoint(&mut self, address: u32) -> Result<usize, ArmError> {
+        let unit = self.first_free_unit()?;
+        self.enable_instruction_watchpoint(unit, address)?;
+        Ok(unit)
+    }
+
+    /// Configures comparator 0 to generate a watchpoint debug event when the
+    /// cycle counter reaches `cycles`.
+    ///
+    /// Only comparator 0 supports matching against `CYCCNT`, and only if the DWT
+    /// implements the cycle counter. The counter has to be running for the
+    /// comparator to match, see [`Dwt::enable`].
+    #[tracing::instrument(skip(self))]
+    pub fn enable_cycle_count_match(&mut self, cycles: u32) -> Result<(), ArmError> {
+        self.check_cycle_counter()?;
+
+        let mut comparator = self.comparator(0)?;
+        comparator.set_address(cycles);
+        comparator.set_mask_bits(0x0);
+        comparator.function.set_emitrange(false);
+        comparator.function.set_datavmatch(false);
+        comparator.function.set_cycmatch(true);
+        comparator.set_function(0b0100);
+        comparator.commit()?;
+
+        // CYCMATCH is RAZ/WI if cycle count matching is not supported.
+        let function = self.load_unit::<Function>(0)?;
+        if !function.cycmatch() {
+            self.store_unit(Function::from(0), 0)?;
+            return Err(ArmError::ExtensionRequired(&["DWT cycle count matching"]));
+        }
+
+        Ok(())
+    }
+
+    /// Configures DWT unit `unit` as a watchpoint on the byte at `address`, which only
+    /// triggers on the `n`-th access of kind `kind`.
+    ///
+    /// The DWT has no counter for comparator matches, comparators can only be linked for data
+    /// value matching. The core therefore halts on every access, and the accesses are counted by
+    /// the debugger with the returned [`NthAccessWatchpoint`], which tells when to resume the core.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_nth_access_watchpoint(
+        &mut self,
+        unit: usize,
+        address: u32,
+        n: u32,
+        kind: WatchKind,
+    ) -> Result<NthAccessWatchpoint, ArmError> {
+        if n == 0 {
+            return Err(ArmError::Other(anyhow!(
+                "A watchpoint can't trigger on the 0th access"
+            )));
+        }
+
+        self.enable_watchpoint(unit, address, 1, kind)?;
+
+        Ok(NthAccessWatchpoint { unit, remaining: n })
+    }
+
+    /// Returns the index of the first unused unit, in the order of the allocation strategy.
+    fn first_free_unit(&mut self) -> Result<usize, ArmError> {
+        self.free_units()?
+            .first()
+            .copied()
+            .ok_or(ArmError::NoFreeComparator)
+    }
+
+    /// Returns the indices of the unused units, in the order of the allocation strategy.
+    ///
+    /// Disabled units holding the address of an enabled data value watchpoint are in use.
+    fn free_units(&mut self) -> Result<Vec<usize>, ArmError> {
+        let mut functions = Vec::new();
+        for unit in 0..self.comparator_count()? as usize {
+            functions.push(self.load_unit::<Function>(unit)?);
+        }
+
+        Ok(self
+            .allocation_order()?
+            .into_iter()
+            .filter(|&unit| is_unused(&functions, unit))
+            .collect())
+    }
+
+    /// Configures watchpoints covering the memory region from `start` up to,
+    /// but not including, `end` and returns the indices of the units used.
+    ///
+    /// A single unit is used if the region is a power of two in size and aligned
+    /// to it. Otherwise the region is split into aligned blocks which each use one
+    /// unit, and [`ArmError::NoFreeComparator`] is returned if not enough units are unused.
+    /// If a block can't be configured, the blocks configured before it are disabled again.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_range_watchpoint(
+        &mut self,
+        start: u32,
+        end: u32,
+        kind: WatchKind,
+    ) -> Result<Vec<usize>, ArmError> {
+        if start >= end {
+            return Err(ArmError::Other(anyhow!(
+                "Invalid watchpoint range {start:#010x}..{end:#010x}"
+            )));
+        }
+
+        let free_units = self.free_units()?;
+        let Some(&first_unit) = free_units.first() else {
+            return Err(ArmError::NoFreeComparator);
+        };
+        let max_mask_size = self.max_mask_size(first_unit)?;
+
+        let blocks = range_blocks(start, end, max_mask_size);
+        if blocks.len() > free_units.len() {
+            return Err(ArmError::NoFreeComparator);
+        }
+
+        for (index, (&unit, &(address, length))) in free_units.iter().zip(&blocks).enumerate() {
+            if let Err(error) = self.enable_watchpoint(unit, address, length, kind) {
+                // The blocks which are already set would only watch a part of the range.
+                for &unit in &free_units[..index] {
+                    if let Err(e) = self.disable_watchpoint(unit) {
+                        tracing::warn!("Failed to disable DWT unit {unit}: {e}");
+                    }
+                }
+
+                return Err(error);
+            }
+        }
+
+        Ok(free_units[..blocks.len()].to_vec())
+    }
+
+    /// Returns the largest mask supported by DWT unit `unit`.
+    ///
+    /// The mask register ignores writes to unimplemented bits, so the maximum is
+    /// determined by writing all ones and reading the value back.
+    fn max_mask_size(&mut self, unit: usize) -> Result<u32, ArmError> {
+        let original = self.load_unit::<Mask>(unit)?;
+
+        self.store_unit(Mask::from(0x1f), unit)?;
+        let max_mask_size = self.load_unit::<Mask>(unit)?.mask();
+
+        self.store_unit(original, unit)?;
+
+        Ok(max_mask_size)
+    }
+
+    /// Returns whether any comparator supports data value matching.
+    ///
+    /// The capability isn't reported in `CTRL`. On comparators without data value matching,
+    /// `DATAVMATCH` in `FUNCTION` reads as zero and ignores writes. An active comparator with
+    /// `DATAVMATCH` set therefore supports it. Otherwise `DATAVMATCH` is set on each disabled
+    /// comparator and read back, after which the register is restored. Writing it doesn't
+    /// enable the comparator, as its function stays disabled.
+    pub fn supports_value_match(&mut self) -> Result<bool, ArmError> {
+        for unit in 0..self.comparator_count()? as usize {
+            let original = self.load_unit::<Function>(unit)?;
+            if original.datavmatch() {
+                return Ok(true);
+            }
+            if original.function() != 0 {
+                continue;
+            }
+
+            if self.unit_capabilities(unit)?.value_match {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Returns the features supported by comparator `unit`.
+    ///
+    /// The comparators of a DWT can differ, for example only comparator 0 of an ARMv7-M DWT
+    /// can match the cycle counter. The features are probed like in
+    /// [`Dwt::supports_value_match`]: `DATAVMATCH` and `CYCMATCH` read as zero and ignore
+    /// writes if unsupported, and `MASK` ignores writes to unimplemented bits. The registers
+    /// are restored afterwards. Probing would change the configuration of an active
+    /// comparator, so `unit` has to be disabled.
+    pub fn unit_capabilities(&mut self, unit: usize) -> Result<UnitCapabilities, ArmError> {
+        self.check_unit(unit)?;
+
+        let original = self.load_unit::<Function>(unit)?;
+        if original.function() != 0 {
+            return Err(ArmError::Other(anyhow!(
+                "DWT unit {unit} is in use, its capabilities can't be probed"
+            )));
+        }
+
+        let mut probe = original;
+        probe.set_datavmatch(true);
+        probe.set_cycmatch(true);
+        self.store_unit(probe, unit)?;
+        let probed = self.load_unit::<Function>(unit)?;
+        self.store_unit(original, unit)?;
+
+        let cyccnt = !self.load::<Ctrl>()?.nocyccnt();
+
+        Ok(UnitCapabilities {
+            value_match: probed.datavmatch(),
+            cycle_match: cyccnt && probed.cycmatch(),
+            max_mask_bits: self.max_mask_size(unit)?,
+        })
+    }
+
+    /// Configures DWT unit `unit` as a watchpoint which only triggers when `value` is
+    /// accessed at `address`.
+    ///
+    /// Data value matching needs two comparators: `unit` compares the data value and has
+    /// to support data value matching, which is usually only the case for comparator 1.
+    /// It is linked to a second, unused comparator which holds `address`. That comparator
+    /// keeps its function disabled, as required by the architecture.
+    ///
+    /// The registers are written in this order:
+    ///
+    /// 1. `COMP` of the address comparator is set to `address`, its `MASK` to 0.
+    /// 2. `COMP` of `unit` is set to `value`, replicated over all bytes for smaller sizes.
+    /// 3. `FUNCTION` of the address comparator is disabled. `FUNCTION` of `unit` gets
+    ///    `DATAVMATCH` set and both `DATAVADDR0` and `DATAVADDR1` pointing to the address
+    ///    comparator.
+    /// 4. `FUNCTION` of `unit` gets `DATAVSIZE` and the function code of `kind`, which
+    ///    enables the watchpoint.
+    ///
+    /// `size` is the size of the access in bytes and has to be 1, 2 or 4.
+    #[tracing::instrument(skip(self))]
+    pub fn enable_data_value_watchpoint(
+        &mut self,
+        unit: usize,
+        address: u32,
+        value: u32,
+        size: u32,
+        kind: WatchKind,
+    ) -> Result<(), ArmError> {
+        // Values smaller than a word have to be replicated over the whole comparator.
+        let (datavsize, value) = match size {
+            1 => (0b00, (value & 0xff) * 0x0101_0101),
+            2 => (0b01, (value & 0xffff) * 0x0001_0001),
+            4 => (0b10, value),
+            _ => return Err(ArmError::InvalidWatchpointLength(size as u64)),
+        };
+
+        if address % size != 0 {
+            return Err(ArmError::alignment_error(address as u64, size as usize));
+        }
+
+        self.check_unit(unit)?;
+
+        let address_unit = self
+            .free_units()?
+            .into_iter()
+            .find(|&candidate| candidate != unit)
+            .ok_or(ArmError::NoFreeComparator)?;
+
+        let mut comparator = self.comparator(address_unit)?;
+        comparator.set_address(address);
+        comparator.set_mask_bits(0x0);
+        comparator.commit()?;
+
+        let mut comparator = self.comparator(unit)?;
+        comparator.set_address(value);
+        comparator.commit()?;
+
+        self.link_comparators(address_unit, unit)?;
+
+        let mut comparator = self.comparator(unit)?;
+        comparator.function.set_datavsize(datavsize);
+        comparator.function.set_emitrange(false);
+        comparator.function.set_cycmatch(false);
+        comparator.set_function(kind.into());
+
+        comparator.commit()
+    }
+
+    /// Links the address comparator `primary` to the data value comparator `value_comp`.
+    ///
+    /// Afterwards, `value_comp` only matches if its data value is accessed at the address
+    /// held by `primary`. The function of `primary` is disabled, as linked address
+    /// comparators must not generate matches by themselves. The value and the function
+    /// of `value_comp` are left to the caller.
+    ///
+    /// If `value_comp` doesn't support data value matching, both comparators are disabled and
+    /// [`ArmError::ExtensionRequired`] is returned.
+    #[tracing::instrument(skip(self))]
+    pub fn link_comparators(&mut self, primary: usize, value_comp: usize) -> Result<(), ArmError> {
+        if primary == value_comp {
+            return Err(ArmError::Other(anyhow!(
+                "DWT unit {primary} cannot be linked to itself"
+            )));
+        }
+
+        self.check_unit(primary)?;
+        self.check_unit(value_comp)?;
+
+        let mut function = self.load_unit::<Function>(primary)?;
+        function.set_function(0x0);
+        self.store_unit(function, primary)?;
+
+        let mut function = self.load_unit::<Function>(value_comp)?;
+        // With a single linked comparator, both links have to point to it.
+        function.set_datavaddr0(primary as u8);
+        function.set_datavaddr1(primary as u8);
+        function.set_datavmatch(true);
+        self.store_unit(function, value_comp)?;
+
+        // DATAVMATCH is RAZ/WI if data value matching is not supported.
+        let function = self.load_unit::<Function>(value_comp)?;
+        if !function.datavmatch() {
+            self.store_unit(Function::from(0), value_comp)?;
+            self.store_unit(Function::from(0), primary)?;
+            return Err(ArmError::ExtensionRequired(&["DWT data value matching"]));
+        }
+
+        Ok(())
+    }
+
+    /// Links a second address comparator `secondary` to the data value comparator
+    /// `value_comp`, which has to be linked with [`Dwt::link_comparators`] first.
+    ///
+    /// `value_comp` then matches if its data value is accessed at either address. Only
+    /// comparators with `LNK1ENA` set support a second link.
+    #[tracing::instrument(skip(self))]
+    pub fn link_second_comparator(
+        &mut self,
+        value_comp: usize,
+        secondary: usize,
+    ) -> Result<(), ArmError> {
+        self.check_unit(value_comp)?;
+        self.check_unit(secondary)?;
+
+        let mut function = self.load_unit::<Function>(value_comp)?;
+        if !function.lnk1ena() {
+            return Err(ArmError::ExtensionRequired(&[
+                "DWT second linked comparator",
+            ]));
+        }
+
+        let mut secondary_function = self.load_unit::<Function>(secondary)?;
+        secondary_function.set_function(0x0);
+        self.store_unit(secondary_function, secondary)?;
+
+        function.set_datavaddr1(secondary as u8);
+        self.store_unit(function, value_comp)
+    }
+
+    /// Reads the `CTRL` register and the registers of all comparators.
+    ///
+    /// The returned state can be written back with [`Dwt::restore`], for example to undo
+    /// temporary changes to a configuration which is also used by the firmware.
+    pub fn snapshot(&mut self) -> Result<DwtState, ArmError> {
+        let ctrl = self.load::<Ctrl>()?;
+
+        let mut comparators = Vec::new();
+        for unit in 0..ctrl.numcomp() as usize {
+            comparators.push(ComparatorState {
+                comp: self.load_unit::<Comp>(unit)?.into(),
+                mask: self.load_unit::<Mask>(unit)?.into(),
+                function: self.load_unit::<Function>(unit)?.into(),
+            });
+        }
+
+        Ok(DwtState {
+            ctrl: ctrl.into(),
+            comparators,
+        })
+    }
+
+    /// Writes back a state read by [`Dwt::snapshot`].
+    ///
+    /// The state has to be taken from a DWT with the same number of comparators. Each
+    /// comparator is disabled while its address and mask are written, and `CTRL` is
+    /// written last. The counters are not part of the state and keep their values.
+    pub fn restore(&mut self, state: &DwtState) -> Result<(), ArmError> {
+        let numcomp = self.comparator_count()? as usize;
+        if state.comparators.len() != numcomp {
+            return Err(ArmError::Other(anyhow!(
+                "The DWT state has {} comparators, but the DWT implements {numcomp}",
+                state.comparators.len()
+            )));
+        }
+
+        for (unit, comparator) in state.comparators.iter().enumerate() {
+            self.store_unit(Function::from(0), unit)?;
+            self.store_unit(Comp::from(comparator.comp), unit)?;
+            self.store_unit(Mask::from(comparator.mask), unit)?;
+            self.store_unit(Function::from(comparator.function), unit)?;
+        }
+
+        self.store(Ctrl::from(state.ctrl))
+    }
+
+    /// Disables all comparators and counters of the DWT.
+    ///
+    /// A reserved cycle counter keeps running, see [`Dwt::reserve_cyccnt`].
+    #[tracing::instrument(skip(self))]
+    pub fn disable_all(&mut self) -> Result<(), ArmError> {
+        for unit in 0..self.comparator_count()? as usize {
+            self.store_unit(Function::from(0), unit)?;
+        }
+
+        let cyccnt_reserved = self.cyccnt_reserved();
+        self.modify_ctrl(|ctrl| {
+            if !cyccnt_reserved {
+                ctrl.set_cyccntena(false);
+            }
+            ctrl.set_cycevtena(false);
+            ctrl.set_foldevtena(false);
+            ctrl.set_lsuevtena(false);
+            ctrl.set_sleepevtena(false);
+            ctrl.set_excevtena(false);
+            ctrl.set_cpievtena(false);
+        })
+    }
+
+    /// Disables all watchpoints until the returned guard is dropped.
+    ///
+    /// This keeps watchpoints from halting the core, for example while stepping over an
+    /// instruction which accesses a watched address. Comparators which generate a debug event
+    /// are disabled, comparators used for data trace keep running. The `FUNCTION` registers are
+    /// read to find the watchpoints, which clears their `MATCHED` bits.
+    #[tracing::instrument(skip(self))]
+    pub fn suspend_watchpoints(&mut self) -> Result<SuspendGuard<'_, 'a>, ArmError> {
+        let numcomp = self.comparator_count()? as usize;
+
+        // If disabling a comparator fails, dropping the guard restores the ones before it.
+        let mut guard = SuspendGuard {
+            dwt: self,
+            suspended: Vec::new(),
+        };
+
+        for unit in 0..numcomp {
+            let function = guard.dwt.load_unit::<Function>(unit)?;
+            if !(0b0100..=0b0111).contains(&function.function()) {
+                continue;
+            }
+
+            let mut disabled = function;
+            disabled.set_function(0);
+            guard.dwt.store_unit(disabled, unit)?;
+            guard.suspended.push((unit, function));
+        }
+
+        Ok(guard)
+    }
+
+    /// Enable exception tracing.
+    pub fn enable_exception_trace(&mut self) -> Result<(), ArmError> {
+        self.modify_ctrl(|ctrl| ctrl.set_exctrcena(true))
+    }
+
+    /// Disable exception tracing.
+    pub fn disable_exception_trace(&mut self) -> Result<(), ArmError> {
+        self.modify_ctrl(|ctrl| ctrl.set_exctrcena(false))
+    }
+
+    /// Enable PC sample trace output
+    ///
+    /// `tap` selects the CYCCNT bit which drives the generation of synchronization packets.
+    pub fn enable_pc_sampling(&mut self, tap: SyncTap) -> Result<(), ArmError> {
+        let mut ctrl = self.load::<Ctrl>()?;
+        if ctrl.notrcpkt() {
+            return Err(ArmError::ExtensionRequired(&["DWT trace sampling"]));
+        }
+
+        ctrl.set_sync_tap(tap);
+        ctrl.set_cyccntena(true);
+        ctrl.set_pcsamplena(true);
+        ctrl.set_cyctap(true);
+        ctrl.set_postpreset(0x3);
+        self.store(ctrl)
+    }
+
+    /// Disable PC sample trace output
+    pub fn disable_pc_sampling(&mut self) -> Result<(), ArmError> {
+        self.modify_ctrl(|ctrl| ctrl.set_pcsamplena(false))
+    }
+
+    /// Sets the interval between PC samples.
+    ///
+    /// A PC sample is emitted every `postpreset + 1` taps of the POSTCNT timer, which are taken
+    /// at CYCCNT\[10\] if `cyctap` is set and at CYCCNT\[6\] otherwise. This results in an
+    /// interval of `(postpreset + 1) * 1024` or `(postpreset + 1) * 64` cycles. `postpreset`
+    /// has to be smaller than 16. The synchronization packets selected by [`SyncTap`] are
+    /// derived from the cycle counter independently of this setting.
+    ///
+    /// [`Dwt::enable_pc_sampling`] selects an interval of 4096 cycles, so call this afterwards.
+    /// The new interval takes effect when the timer is reloaded the next time.
+    pub fn set_pc_sample_rate(&mut self, cyctap: bool, postpreset: u8) -> Result<(), ArmError> {
+        if postpreset > 0xF {
+            return Err(ArmError::Other(anyhow!(
+                "The POSTCNT reload value {postpreset} does not fit into 4 bits"
+            )));
+        }
+
+        self.modify_ctrl(|ctrl| {
+            ctrl.set_cyctap(cyctap);
+            ctrl.set_postpreset(postpreset.into());
+            // POSTINIT must not be written while the timer is in use.
+            if !ctrl.pcsamplena() && !ctrl.cycevtena() {
+                ctrl.set_postinit(postpreset);
+            }
+        })
+    }
+
+    /// Enables the cycle counter and PC sampling at the rate requested by `config`, and returns
+    /// the effective sample rate in Hz.
+    ///
+    /// The POSTCNT interval closest to `core_clock / sample_rate` cycles is selected, see
+    /// [`Dwt::set_pc_sample_rate`]. As the interval ranges from 64 to 16384 cycles, the
+    /// effective rate can differ considerably from the requested one. The cycle counter is not
+    /// reset.
+    pub fn configure_for_profiling(&mut self, config: ProfilingConfig) -> Result<u32, ArmError> {
+        if config.core_clock == 0 || config.sample_rate == 0 {
+            return Err(ArmError::Other(anyhow!(
+                "The core clock and the sample rate have to be non-zero, got {config:?}"
+            )));
+        }
+
+        self.check_cycle_counter()?;
+        let mut ctrl = self.load::<Ctrl>()?;
+        if ctrl.notrcpkt() {
+            return Err(ArmError::ExtensionRequired(&["DWT trace sampling"]));
+        }
+
+        let target = config.core_clock / config.sample_rate;
+        let (cyctap, postpreset, interval) = (0..16u32)
+            .flat_map(|postpreset| {
+                [
+                    (false, postpreset, (postpreset + 1) * 64),
+                    (true, postpreset, (postpreset + 1) * 1024),
+                ]
+            })
+            .min_by_key(|(_, _, interval)| interval.abs_diff(target))
+            .unwrap();
+
+        // POSTINIT must not be written while the timer is in use, so sampling is stopped first.
+        ctrl.set_pcsamplena(false);
+        ctrl.set_cyccntena(true);
+        ctrl.set_sync_tap(config.sync_tap);
+        ctrl.set_cyctap(cyctap);
+        ctrl.set_postpreset(postpreset);
+        ctrl.set_postinit(postpreset as u8);
+        self.store(ctrl)?;
+
+        ctrl.set_pcsamplena(true);
+        self.store(ctrl)?;
+
+        Ok(config.core_clock / interval)
+    }
+
+    /// Reads the current value of the cycle counter.
+    ///
+    /// The counter only increments while `CYCCNTENA` is set, see [`Dwt::enable`].
+    pub fn read_cycle_count(&mut self) -> Result<u32, ArmError> {
+        self.check_cycle_counter()?;
+
+        let cyccnt = self.load::<Cyccnt>()?;
+        Ok(cyccnt.into())
+    }
+
+    /// Resets the cycle counter to zero.
+    ///
+    /// This fails while the cycle counter is reserved, see [`Dwt::reserve_cyccnt`].
+    pub fn reset_cycle_count(&mut self) -> Result<(), ArmError> {
+        self.check_cycle_counter()?;
+        self.check_cyccnt_unreserved()?;
+
+        self.store(Cyccnt::from(0))
+    }
+
+    /// Reserves the cycle counter, so other users of the DWT don't reset or disable it.
+    ///
+    /// Firmware can rely on `CYCCNT`, for example for defmt timestamps. While the returned
+    /// reservation exists, [`Dwt::reset_cycle_count`] and [`CycleCounter::new`] fail, and
+    /// [`Dwt::disable_all`] keeps the counter enabled. Reading the counter is still possible.
+    ///
+    /// The reservation is recorded in the [`CyccntReservations`] of this `Dwt`, and applies to
+    /// all [`Dwt`]s sharing them through [`Dwt::set_cyccnt_reservations`], such as those of a
+    /// [`Session`](crate::Session). It is released when it is dropped or with
+    /// [`CyccntReservation::release`]. Only one reservation can exist at a time. Writes to
+    /// `CTRL` or `CYCCNT` through other means, like [`Dwt::restore`] or
+    /// [`Dwt::write_raw_register`], are not prevented.
+    pub fn reserve_cyccnt(&mut self) -> Result<CyccntReservation, ArmError> {
+        self.check_cycle_counter()?;
+
+        let base_address = self.component.component.id().component_address();
+        if !self.reservations.0.lock().unwrap().insert(base_address) {
+            return Err(ArmError::Other(anyhow!(
+                "The DWT cycle counter is already reserved"
+            )));
+        }
+
+        Ok(CyccntReservation {
+            reservations: self.reservations.clone(),
+            base_address,
+        })
+    }
+
+    fn cyccnt_reserved(&self) -> bool {
+        let base_address = self.component.component.id().component_address();
+        self.reservations.0.lock().unwrap().contains(&base_address)
+    }
+
+    fn check_cyccnt_unreserved(&self) -> Result<(), ArmError> {
+        if self.cyccnt_reserved() {
+            return Err(ArmError::Other(anyhow!(
+                "The DWT cycle counter is reserved"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Enables the exception overhead counter.
+    pub fn enable_exception_counter(&mut self) -> Result<(), ArmError> {
+        self.enable_profiling_counter(Ctrl::excevtena, Ctrl::set_excevtena)
+    }
+
+    /// Reads the exception overhead counter.
+    ///
+    /// The counter increments on every cycle spent in exception entry and exit,
+    /// and wraps around at 8 bits.
+    pub fn read_exception_count(&mut self) -> Result<u8, ArmError> {
+        self.check_profiling_counters()?;
+
+        let exccnt = self.load::<Exccnt>()?;
+        Ok(exccnt.exccnt())
+    }
+
+    /// Resets the exception overhead counter to zero.
+    pub fn reset_exception_count(&mut self) -> Result<(), ArmError> {
+        self.check_profiling_counters()?;
+
+        self.store(Exccnt::from(0))
+    }
+
+    /// Enables the CPI counter and reads its current value.
+    ///
+    /// The counter increments on every additional cycle required to execute
+    /// multi-cycle instructions, and wraps around at 8 bits.
+    pub fn read_cpi_count(&mut self) -> Result<u8, ArmError> {
+        self.enable_profiling_counter(Ctrl::cpievtena, Ctrl::set_cpievtena)?;
+
+        let cpicnt = self.load::<Cpicnt>()?;
+        Ok(cpicnt.cpicnt())
+    }
+
+    /// Enables the LSU counter and reads its current value.
+    ///
+    /// The counter increments on every additional cycle required to execute
+    /// load or store instructions, and wraps around at 8 bits.
+    pub fn read_lsu_count(&mut self) -> Result<u8, ArmError> {
+        self.enable_profiling_counter(Ctrl::lsuevtena, Ctrl::set_lsuevtena)?;
+
+        let lsucnt = self.load::<Lsucnt>()?;
+        Ok(lsucnt.lsucnt())
+    }
+
+    /// Enables the folded instruction counter and reads its current value.
+    ///
+    /// The counter increments on every instruction that takes zero cycles,
+    /// and wraps around at 8 bits.
+    pub fn read_folded_count(&mut self) -> Result<u8, ArmError> {
+        self.enable_profiling_counter(Ctrl::foldevtena, Ctrl::set_foldevtena)?;
+
+        let foldcnt = self.load::<Foldcnt>()?;
+        Ok(foldcnt.foldcnt())
+    }
+
+    /// Enables the sleep counter and reads its current value.
+    ///
+    /// The counter increments on every cycle the processor is sleeping,
+    /// and wraps around at 8 bits.
+    pub fn read_sleep_count(&mut self) -> Result<u8, ArmError> {
+        self.enable_profiling_counter(Ctrl::sleepevtena, Ctrl::set_sleepevtena)?;
+
+        let sleepcnt = self.load::<Sleepcnt>()?;
+        Ok(sleepcnt.sleepcnt())
+    }
+
+    /// Sets the enable bit of a profiling counter in `CTRL`, unless it is set already.
+    fn enable_profiling_counter(
+        &mut self,
+        enabled: fn(&Ctrl) -> bool,
+        set_enabled: fn(&mut Ctrl, bool),
+    ) -> Result<(), ArmError> {
+        let mut ctrl = self.load::<Ctrl>()?;
+        if ctrl.noprfcnt() {
+            return Err(ArmError::ExtensionRequired(&["DWT profiling counters"]));
+        }
+
+        if !enabled(&ctrl) {
+            set_enabled(&mut ctrl, true);
+            self.store(ctrl)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns an error if the DWT does not implement the cycle counter.
+    fn check_cycle_counter(&mut self) -> Result<(), ArmError> {
+        let ctrl = self.load::<Ctrl>()?;
+        if ctrl.nocyccnt() {
+            return Err(ArmError::ExtensionRequired(&["DWT cycle counter"]));
+        }
+
+        Ok(())
+    }
+
+    /// Returns an error if the DWT does not implement the profiling counters.
+    fn check_profiling_counters(&mut self) -> Result<(), ArmError> {
+        let ctrl = self.load::<Ctrl>()?;
+        if ctrl.noprfcnt() {
+            return Err(ArmError::ExtensionRequired(&["DWT profiling counters"]));
+        }
+
+        Ok(())
+    }
+}
+
+/// The `COMP`, `MASK` and `FUNCTION` registers of a single DWT comparator.
+///
+/// The registers are modified locally and written back together by [`Comparator::commit`].
+/// Create one with [`Dwt::comparator`].
+pub struct Comparator<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+    access_time_limit: Option<Duration>,
+    #[cfg(feature = "dwt-stats")]
+    stats: &'a mut DwtTransferStats,
+    unit: usize,
+    comp: Comp,
+    mask: Mask,
+    function: Function,
+}
+
+impl Comparator<'_> {
+    /// Returns the index of the comparator within the DWT.
+    pub fn unit(&self) -> usize {
+        self.unit
+    }
+
+    /// Sets the value of the `COMP` register, the address or data value to compare against.
+    pub fn set_address(&mut self, address: u32) {
+        self.comp.set_comp(address);
+    }
+
+    /// Sets the number of low address bits which are ignored by the comparison.
+    pub fn set_mask_bits(&mut self, bits: u32) {
+        self.mask.set_mask(bits);
+    }
+
+    /// Sets the `FUNCTION` field, which selects the action taken on a match.
+    ///
+    /// A value of zero disables the comparator.
+    pub fn set_function(&mut self, function: u32) {
+        self.function.set_function(function);
+    }
+
+    /// Writes the registers back to the DWT.
+    ///
+    /// If a write fails, the comparator is disabled, and [`ArmError::DwtComparator`] tells
+    /// which register could not be written.
+    #[tracing::instrument(skip_all, fields(unit = self.unit))]
+    pub fn commit(mut self) -> Result<(), ArmError> {
+        let (comp, mask, function) = (self.comp, self.mask, self.function);
+        let result = self
+            .store(comp)
+            .and_then(|()| self.store(mask))
+            .and_then(|()| self.store(function));
+
+        if result.is_err() {
+            // A half-configured comparator would match unexpected accesses.
+            let _ = self.store(Function::from(0));
+        }
+
+        result
+    }
+
+    fn store<R: DebugComponentInterface>(&mut self, register: R) -> Result<(), ArmError> {
+        #[cfg(feature = "dwt-stats")]
+        {
+            self.stats.writes += 1;
+        }
+
+        reject_slow_access(self.access_time_limit, || {
+            register.store_unit(self.component, self.interface, self.unit)
+        })
+        .map_err(|source| ArmError::DwtComparator {
+            register: R::NAME,
+            unit: self.unit,
+            source: Box::new(source),
+        })
+    }
+}
+
+/// Restores the watchpoints disabled by [`Dwt::suspend_watchpoints`] when dropped.
+///
+/// Errors while restoring on drop are logged. Use [`SuspendGuard::restore`] to handle them.
+pub struct SuspendGuard<'d, 'a> {
+    dwt: &'d mut Dwt<'a>,
+    suspended: Vec<(usize, Function)>,
+}
+
+impl<'a> SuspendGuard<'_, 'a> {
+    /// Returns the DWT, to access it while the watchpoints are suspended.
+    pub fn dwt(&mut self) -> &mut Dwt<'a> {
+        self.dwt
+    }
+
+    /// Returns the indices of the suspended comparators.
+    pub fn suspended_units(&self) -> Vec<usize> {
+        self.suspended.iter().map(|&(unit, _)| unit).collect()
+    }
+
+    /// Restores the suspended watchpoints.
+    ///
+    /// All comparators are restored even if one fails, and the first error is returned.
+    pub fn restore(mut self) -> Result<(), ArmError> {
+        self.restore_all()
+    }
+
+    fn restore_all(&mut self) -> Result<(), ArmError> {
+        let mut result = Ok(());
+        for (unit, function) in std::mem::take(&mut self.suspended) {
+            if let Err(err) = self.dwt.store_unit(function, unit) {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl Drop for SuspendGuard<'_, '_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.restore_all() {
+            tracing::warn!("Failed to restore the suspended DWT watchpoints: {}", err);
+        }
+    }
+}
+
+/// The DWTs whose cycle counter is reserved, see [`Dwt::reserve_cyccnt`].
+///
+/// Clones share the same reservations.
+#[derive(Debug, Clone, Default)]
+pub struct CyccntReservations(Arc<Mutex<HashSet<u64>>>);
+
+/// A reservation of the DWT cycle counter, see [`Dwt::reserve_cyccnt`].
+///
+/// The cycle counter is released when this is dropped.
+#[derive(Debug)]
+pub struct CyccntReservation {
+    reservations: CyccntReservations,
+    base_address: u64,
+}
+
+impl CyccntReservation {
+    /// Releases the cycle counter.
+    pub fn release(self) {}
+}
+
+impl Drop for CyccntReservation {
+    fn drop(&mut self) {
+        self.reservations
+            .0
+            .lock()
+            .unwrap()
+            .remove(&self.base_address);
+    }
+}
+
+/// Measures elapsed core cycles using the DWT cycle counter.
+///
+/// Creating the counter enables the cycle counter and resets it to zero. Call
+/// [`CycleCounter::restart`] to begin another measurement; the counter is only
+/// enabled once. If it was disabled before, it is disabled again on drop.
+pub struct CycleCounter<'a> {
+    dwt: Dwt<'a>,
+    enabled_by_us: bool,
+}
+
+impl<'a> CycleCounter<'a> {
+    /// Enables and resets the cycle counter of `dwt`.
+    ///
+    /// This fails while the cycle counter is reserved, see [`Dwt::reserve_cyccnt`].
+    pub fn new(mut dwt: Dwt<'a>) -> Result<Self, ArmError> {
+        dwt.check_cycle_counter()?;
+        dwt.check_cyccnt_unreserved()?;
+
+        let mut ctrl = dwt.load::<Ctrl>()?;
+        let enabled_by_us = !ctrl.cyccntena();
+        if enabled_by_us {
+            ctrl.set_cyccntena(true);
+            dwt.store(ctrl)?;
+        }
+
+        let mut counter = Self { dwt, enabled_by_us };
+        counter.restart()?;
+
+        Ok(counter)
+    }
+
+    /// Resets the cycle counter to zero to begin a new measurement.
+    ///
+    /// This fails while the cycle counter is reserved, see [`Dwt::reserve_cyccnt`].
+    pub fn restart(&mut self) -> Result<(), ArmError> {
+        self.dwt.check_cyccnt_unreserved()?;
+
+        self.dwt.store(Cyccnt::from(0))
+    }
+
+    /// Returns the number of cycles elapsed since the counter was created or restarted.
+    ///
+    /// The counter wraps around at 32 bits.
+    pub fn elapsed(&mut self) -> Result<u32, ArmError> {
+        let cyccnt = self.dwt.load::<Cyccnt>()?;
+        Ok(cyccnt.into())
+    }
+}
+
+impl Drop for CycleCounter<'_> {
+    fn drop(&mut self) {
+        if !self.enabled_by_us {
+            return;
+        }
+
+        let result = self.dwt.load::<Ctrl>().and_then(|mut ctrl| {
+            ctrl.set_cyccntena(false);
+            self.dwt.store(ctrl)
+        });
+        if let Err(err) = result {
+            tracing::warn!("Failed to disable the DWT cycle counter: {}", err);
+        }
+    }
+}
+
+/// Performs the register access `access`, failing with [`ArmError::Timeout`] if it took longer
+/// than `limit`.
+///
+/// The access always runs to completion, the duration is only checked after it returned.
+fn reject_slow_access<T>(
+    limit: Option<Duration>,
+    access: impl FnOnce() -> Result<T, ArmError>,
+) -> Result<T, ArmError> {
+    let start = Instant::now();
+    let result = access()?;
+
+    match limit {
+        Some(limit) if start.elapsed() > limit => Err(ArmError::Timeout),
+        _ => Ok(result),
+    }
+}
+
+/// Checks that a comparator can match the region of `length` bytes at `address`.
+///
+/// The comparator ignores the low address bits, so `length` has to be a power
+/// of two and `address` has to be aligned to it.
+fn check_region(address: u32, length: u32) -> Result<(), ArmError> {
+    if !length.is_power_of_two() {
+        return Err(ArmError::InvalidWatchpointLength(length as u64));
+    }
+
+    if address % length != 0 {
+        return Err(ArmError::alignment_error(address as u64, length as usize));
+    }
+
+    Ok(())
+}
+
+/// Splits the region from `start` up to `end` into blocks which are a power of two
+/// in size, aligned to their size and no larger than `1 << max_mask_size` bytes.
+///
+/// Returns the start address and the length of each block.
+fn range_blocks(start: u32, end: u32, max_mask_size: u32) -> Vec<(u32, u32)> {
+    let end = end as u64;
+    let mut address = start as u64;
+    let mut blocks = Vec::new();
+
+    while address < end {
+        let mut size = 1u64 << address.trailing_zeros().min(max_mask_size);
+        while address + size > end {
+            size >>= 1;
+        }
+
+        blocks.push((address as u32, size as u32));
+        address += size;
+    }
+
+    blocks
+}
+
+impl DebugComponentInterface for Ctrl {}
+
+impl DebugComponentInterface for Cyccnt {}
+
+impl DebugComponentInterface for Cpicnt {}
+
+impl DebugComponentInterface for Exccnt {}
+
+impl DebugComponentInterface for Sleepcnt {}
+
+impl DebugComponentInterface for Lsucnt {}
+
+impl DebugComponentInterface for Foldcnt {}
+
+impl DebugComponentInterface for Comp {}
+
+impl DebugComponentInterface for Mask {}
+
+impl DebugComponentInterface for Function {}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        cortex_m_matched_watchpoint, range_blocks, reject_slow_access, AllocationStrategy,
+        CycleCounter, Dwt, EmitOptions, ProfilingConfig, SyncTap, UnitCapabilities, WatchKind,
+        WatchSize, WatchpointBuilder, WatchpointConfig, CORTEX_M_DWT_BASE,
+    };
+    use crate::architecture::arm::component::mock::MockDwt;
+    use crate::architecture::arm::ArmError;
+    use crate::test::MockMemory;
+
+    #[test]
+    fn data_value_watchpoint_links_address_comparator() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.allocate_watchpoint(0x2000_0000, 4, WatchKind::Write)
+            .unwrap();
+        dwt.enable_data_value_watchpoint(1, 0x2000_0100, 0xef, 1, WatchKind::Write)
+            .unwrap();
+
+        // Unit 0 is in use, so the address is placed in unit 2
+        assert_eq!(mock.interface.read(0x40), 0x2000_0100);
+        assert_eq!(mock.interface.read(0x30), 0xefef_efef);
+
+        let function = mock.interface.read(0x38);
+        assert_eq!(function & 0xf, 0b0110);
+        // DATAVMATCH
+        assert_eq!(function >> 8 & 0x1, 1);
+        // DATAVSIZE
+        assert_eq!(function >> 10 & 0x3, 0b00);
+        // DATAVADDR0
+        assert_eq!(function >> 12 & 0xf, 2);
+    }
+
+    #[test]
+    fn linked_address_comparator_is_not_allocated() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable_data_value_watchpoint(1, 0x2000_0100, 0xef, 1, WatchKind::Write)
+            .unwrap();
+        assert_eq!(dwt.free_comparator_count().unwrap(), 2);
+
+        // Unit 0 holds the address, so the next watchpoints use units 2 and 3.
+        let units = [
+            dwt.allocate_watchpoint(0x2000_0200, 4, WatchKind::Read)
+                .unwrap(),
+            dwt.allocate_watchpoint(0x2000_0300, 4, WatchKind::Read)
+                .unwrap(),
+        ];
+        assert_eq!(units, [2, 3]);
+        assert!(matches!(
+            dwt.allocate_watchpoint(0x2000_0400, 4, WatchKind::Read),
+            Err(ArmError::NoFreeComparator)
+        ));
+
+        assert_eq!(mock.interface.read(0x20), 0x2000_0100);
+    }
+
+    #[test]
+    fn data_value_watchpoint_register_encoding() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable_data_value_watchpoint(1, 0x2000_0010, 0x1234, 2, WatchKind::ReadWrite)
+            .unwrap();
+        assert!(matches!(
+            dwt.enable_data_value_watchpoint(1, 0x2000_0010, 0, 3, WatchKind::Write),
+            Err(ArmError::InvalidWatchpointLength(3))
+        ));
+
+        // The address comparator 0 matches the exact address and is disabled itself.
+        assert_eq!(mock.interface.read(0x20), 0x2000_0010);
+        assert_eq!(mock.interface.read(0x24), 0);
+        assert_eq!(mock.interface.read(0x28), 0);
+
+        // DATAVADDR1 = 0, DATAVADDR0 = 0, DATAVSIZE = halfword, DATAVMATCH, read/write
+        assert_eq!(mock.interface.read(0x30), 0x1234_1234);
+        assert_eq!(mock.interface.read(0x38), 0b01 << 10 | 1 << 8 | 0b0111);
+    }
+
+    #[test]
+    fn data_value_watchpoint_requires_value_matching() {
+        let mut mock = MockDwt::new(4);
+        // DATAVMATCH of unit 1 is not implemented.
+        mock.interface.implement_bits(0x38, !(1 << 8));
+
+        let mut dwt = mock.dwt();
+        assert!(matches!(
+            dwt.enable_data_value_watchpoint(1, 0x2000_0010, 0x1234, 4, WatchKind::Write),
+            Err(ArmError::ExtensionRequired(_))
+        ));
+
+        // Neither unit matches, and the address comparator is free again.
+        assert_eq!(dwt.free_comparator_count().unwrap(), 4);
+        assert_eq!(mock.interface.read(0x28), 0);
+        assert_eq!(mock.interface.read(0x38), 0);
+    }
+
+    #[test]
+    fn data_value_watchpoint_requires_two_comparators() {
+        let mut mock = MockDwt::new(1);
+        let mut dwt = mock.dwt();
+
+        assert!(dwt
+            .enable_data_value_watchpoint(0, 0x2000_0000, 0, 4, WatchKind::Write)
+            .is_err());
+    }
+
+    #[test]
+    fn range_blocks_are_aligned() {
+        assert_eq!(
+            range_blocks(0x2000_0000, 0x2000_0100, 31),
+            [(0x2000_0000, 0x100)]
+        );
+        assert_eq!(
+            range_blocks(0x2000_0004, 0x2000_0016, 31),
+            [
+                (0x2000_0004, 0x4),
+                (0x2000_0008, 0x8),
+                (0x2000_0010, 0x4),
+                (0x2000_0014, 0x2)
+            ]
+        );
+        assert_eq!(
+            range_blocks(0x2000_0000, 0x2000_0040, 4),
+            [
+                (0x2000_0000, 0x10),
+                (0x2000_0010, 0x10),
+                (0x2000_0020, 0x10),
+                (0x2000_0030, 0x10)
+            ]
+        );
+    }
+
+    #[test]
+    fn range_watchpoint_uses_multiple_units() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        let units = dwt
+            .enable_range_watchpoint(0x2000_0004, 0x2000_0010, WatchKind::Read)
+            .unwrap();
+        assert_eq!(units, [0, 1]);
+
+        // Not enough units left for five blocks
+        assert!(dwt
+            .enable_range_watchpoint(0x2000_0101, 0x2000_0110, WatchKind::Read)
+            .is_err());
+
+        assert_eq!(mock.interface.read(0x20), 0x2000_0004);
+        assert_eq!(mock.interface.read(0x24), 2);
+        assert_eq!(mock.interface.read(0x30), 0x2000_0008);
+        assert_eq!(mock.interface.read(0x34), 3);
+        assert_eq!(mock.interface.read(0x48), 0);
+    }
+
+    #[test]
+    fn failed_range_watchpoint_disables_configured_blocks() {
+        let mut mock = MockDwt::new(4);
+        mock.interface.fail_write(0x30);
+
+        let mut dwt = mock.dwt();
+        assert!(dwt
+            .enable_range_watchpoint(0x2000_0004, 0x2000_0010, WatchKind::Read)
+            .is_err());
+        assert_eq!(dwt.free_comparator_count().unwrap(), 4);
+
+        assert_eq!(mock.interface.read(0x28), 0);
+        assert_eq!(mock.interface.read(0x38), 0);
+    }
+
+    #[test]
+    fn disable_all_clears_comparators() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable().unwrap();
+        dwt.enable_range_watchpoint(0x2000_0004, 0x2000_0010, WatchKind::Write)
+            .unwrap();
+        dwt.enable_data_value_watchpoint(2, 0x2000_0100, 0x1234, 2, WatchKind::Read)
+            .unwrap();
+
+        dwt.disable_all().unwrap();
+
+        for unit in 0..4 {
+            assert_eq!(mock.interface.read(0x28 + 16 * unit), 0);
+        }
+        // CYCCNTENA
+        assert_eq!(mock.interface.read(0x0) & 0x1, 0);
+    }
+
+    #[test]
+    fn watch_kind_from_str() {
+        for kind in [WatchKind::Read, WatchKind::Write, WatchKind::ReadWrite] {
+            assert_eq!(kind.to_string().parse::<WatchKind>().unwrap(), kind);
+        }
+
+        assert_eq!("RW".parse::<WatchKind>().unwrap(), WatchKind::ReadWrite);
+        assert_eq!("Write".parse::<WatchKind>().unwrap(), WatchKind::Write);
+        assert_eq!("access".parse::<WatchKind>().unwrap(), WatchKind::ACCESS);
+        assert!("execute".parse::<WatchKind>().is_err());
+    }
+
+    #[test]
+    fn watch_kind_round_trip() {
+        for kind in [WatchKind::Read, WatchKind::Write, WatchKind::ReadWrite] {
+            assert_eq!(WatchKind::try_from(u32::from(kind)).unwrap(), kind);
+        }
+
+        // Data trace function codes are not watchpoints
+        assert!(WatchKind::try_from(0b0011).is_err());
+        assert!(WatchKind::try_from(0).is_err());
+    }
+
+    #[test]
+    fn data_trace_range_sets_mask() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable_data_trace_range(1, 0x2000_0040, 32).unwrap();
+        assert!(dwt.enable_data_trace_range(2, 0x2000_0044, 32).is_err());
+        assert!(dwt.enable_data_trace_range(2, 0x2000_0040, 24).is_err());
+
+        assert_eq!(mock.interface.read(0x30), 0x2000_0040);
+        assert_eq!(mock.interface.read(0x34), 5);
+        assert_eq!(mock.interface.read(0x38) & 0xf, 0b11);
+        // DATAVSIZE is word
+        assert_eq!((mock.interface.read(0x38) >> 10) & 0b11, 0b10);
+        assert_eq!(mock.interface.read(0x48), 0);
+    }
+
+    #[test]
+    fn data_trace_emit_options() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        let options = EmitOptions {
+            emit_range: true,
+            emit_data: true,
+            emit_pc: false,
+        };
+        dwt.enable_data_trace_with_options(0, 0x2000_0000, options)
+            .unwrap();
+
+        let options = EmitOptions {
+            emit_pc: true,
+            ..options
+        };
+        assert!(dwt
+            .enable_data_trace_with_options(1, 0x2000_0000, options)
+            .is_err());
+        assert!(dwt
+            .enable_data_trace_with_options(1, 0x2000_0000, EmitOptions::default())
+            .is_err());
+
+        // EMITRANGE and FUNCTION
+        assert_eq!(mock.interface.read(0x28) & 0x2f, 0x22);
+        assert_eq!(mock.interface.read(0x38), 0);
+    }
+
+    #[test]
+    fn data_trace_function_codes() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        let pc = EmitOptions {
+            emit_pc: true,
+            ..Default::default()
+        };
+        let data = EmitOptions {
+            emit_data: true,
+            ..Default::default()
+        };
+        dwt.enable_data_trace_with_options(0, 0x2000_0000, pc)
+            .unwrap();
+        dwt.enable_data_trace_with_options(1, 0x2000_0000, data)
+            .unwrap();
+        dwt.enable_data_trace(2, 0x2000_0000).unwrap();
+
+        assert_eq!(mock.interface.read(0x28) & 0x2f, 0b0001);
+        assert_eq!(mock.interface.read(0x38) & 0x2f, 0b0010);
+        assert_eq!(mock.interface.read(0x48) & 0x2f, 0b0011);
+    }
+
+    #[test]
+    fn exhausted_comparators_are_reported() {
+        let mut mock = MockDwt::new(1);
+        let mut dwt = mock.dwt();
+
+        dwt.allocate_watchpoint(0x2000_0000, 4, WatchKind::Write)
+            .unwrap();
+        assert!(matches!(
+            dwt.allocate_instruction_watchpoint(0x0800_0000),
+            Err(ArmError::NoFreeComparator)
+        ));
+        assert!(matches!(
+            dwt.enable_range_watchpoint(0x2000_0100, 0x2000_0104, WatchKind::Read),
+            Err(ArmError::NoFreeComparator)
+        ));
+    }
+
+    #[test]
+    fn out_of_range_unit_is_rejected() {
+        let mut mock = MockDwt::new(2);
+        let mut dwt = mock.dwt();
+
+        assert!(matches!(
+            dwt.enable_watchpoint(2, 0x2000_0000, 4, WatchKind::Write),
+            Err(ArmError::OutOfBounds)
+        ));
+        assert!(matches!(
+            dwt.disable_watchpoint(2),
+            Err(ArmError::OutOfBounds)
+        ));
+        assert!(matches!(
+            dwt.link_comparators(0, 5),
+            Err(ArmError::OutOfBounds)
+        ));
+
+        // The registers of unit 2 are beyond the comparators of the DWT
+        assert_eq!(mock.interface.read(0x40), 0);
+        assert_eq!(mock.interface.read(0x48), 0);
+    }
+
+    #[test]
+    fn instruction_watchpoint_uses_free_unit() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.allocate_watchpoint(0x2000_0000, 4, WatchKind::Read)
+            .unwrap();
+        let unit = dwt.allocate_instruction_watchpoint(0x0000_1234).unwrap();
+        assert_eq!(unit, 1);
+
+        assert_eq!(mock.interface.read(0x30), 0x0000_1234);
+        assert_eq!(mock.interface.read(0x38) & 0xf, 0b0100);
+    }
+
+    #[test]
+    fn cycle_count_match_uses_comparator_0() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable_cycle_count_match(1000).unwrap();
+
+        assert_eq!(mock.interface.read(0x20), 1000);
+        // CYCMATCH and FUNCTION
+        assert_eq!(mock.interface.read(0x28) & 0x8f, 0x84);
+
+        // NOCYCCNT
+        mock.interface.write(0x0, 4 << 28 | 1 << 25);
+        let mut dwt = mock.dwt();
+        assert!(matches!(
+            dwt.enable_cycle_count_match(1000),
+            Err(ArmError::ExtensionRequired(_))
+        ));
+    }
+
+    #[test]
+    fn slow_access_times_out() {
+        let limit = Some(std::time::Duration::from_millis(1));
+
+        assert!(reject_slow_access(limit, || Ok(())).is_ok());
+        assert!(matches!(
+            reject_slow_access(limit, || {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                Ok(())
+            }),
+            Err(ArmError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn raw_register_access() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.write_raw_register(0x100, 0x1234_5678).unwrap();
+        assert_eq!(dwt.read_raw_register(0x100).unwrap(), 0x1234_5678);
+        assert_eq!(dwt.read_raw_register(0x0).unwrap(), 4 << 28);
+
+        assert_eq!(mock.interface.read(0x100), 0x1234_5678);
+    }
+
+    #[test]
+    fn u64_watchpoint_address_is_range_checked() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        assert!(matches!(
+            dwt.enable_watchpoint_u64(0, 0x1_2000_0000, 4, WatchKind::Write),
+            Err(ArmError::AddressOutOf32BitAddressSpace)
+        ));
+        assert_eq!(mock.interface.read(0x28), 0);
+
+        let mut dwt = mock.dwt();
+        dwt.enable_watchpoint_u64(0, 0x2000_0000, 4, WatchKind::Write)
+            .unwrap();
+        assert_eq!(mock.interface.read(0x20), 0x2000_0000);
+    }
+
+    #[test]
+    fn builder_configures_comparator() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        WatchpointBuilder::new()
+            .address(0x2000_0000)
+            .length(16)
+            .kind(WatchKind::Read)
+            .size(WatchSize::Word)
+            .emit_range(true)
+            .apply(&mut dwt, 2)
+            .unwrap();
+
+        assert_eq!(mock.interface.read(0x40), 0x2000_0000);
+        assert_eq!(mock.interface.read(0x44), 4);
+        // DATAVSIZE word, EMITRANGE and read watchpoint
+        assert_eq!(mock.interface.read(0x48), 0b10 << 10 | 1 << 5 | 0b0101);
+    }
+
+    #[test]
+    fn builder_value_match_links_comparators() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        WatchpointBuilder::new()
+            .address(0x2000_0010)
+            .kind(WatchKind::Write)
+            .size(WatchSize::Halfword)
+            .value_match(0x1234)
+            .apply(&mut dwt, 1)
+            .unwrap();
+
+        // The value is replicated over the data value comparator, the address is held by
+        // the first unused comparator.
+        assert_eq!(mock.interface.read(0x30), 0x1234_1234);
+        assert_eq!(mock.interface.read(0x20), 0x2000_0010);
+    }
+
+    #[test]
+    fn builder_rejects_illegal_combinations() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        let builder = WatchpointBuilder::new()
+            .address(0x2000_0000)
+            .kind(WatchKind::Write);
+
+        assert!(WatchpointBuilder::new()
+            .kind(WatchKind::Write)
+            .apply(&mut dwt, 0)
+            .is_err());
+        assert!(WatchpointBuilder::new()
+            .address(0x2000_0000)
+            .apply(&mut dwt, 0)
+            .is_err());
+        assert!(builder
+            .value_match(1)
+            .emit_range(true)
+            .apply(&mut dwt, 1)
+            .is_err());
+        assert!(builder.value_match(1).length(8).apply(&mut dwt, 1).is_err());
+        assert!(builder.length(3).apply(&mut dwt, 0).is_err());
+
+        for unit in 0..4 {
+            assert_eq!(mock.interface.read(0x28 + 16 * unit), 0);
+        }
+    }
+
+    #[test]
+    fn modify_ctrl_keeps_other_bits() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.modify_ctrl(|ctrl| {
+            ctrl.set_cyccntena(true);
+            ctrl.set_exctrcena(true);
+        })
+        .unwrap();
+
+        // NUMCOMP, EXCTRCENA and CYCCNTENA
+        assert_eq!(mock.interface.read(0x0), 4 << 28 | 1 << 16 | 1);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable().unwrap();
+        dwt.enable_watchpoint(1, 0x2000_0010, 16, WatchKind::Write)
+            .unwrap();
+        let state = dwt.snapshot().unwrap();
+        assert_eq!(state.comparators.len(), 4);
+
+        dwt.disable_all().unwrap();
+        dwt.allocate_instruction_watchpoint(0x0800_0000).unwrap();
+        dwt.restore(&state).unwrap();
+
+        assert_eq!(dwt.snapshot().unwrap(), state);
+        assert_eq!(mock.interface.read(0x30), 0x2000_0010);
+        assert_eq!(mock.interface.read(0x38), 0b0110);
+
+        // A state of a DWT with a different number of comparators is rejected.
+        let mut dwt = mock.dwt();
+        let mut state = state;
+        state.comparators.pop();
+        assert!(dwt.restore(&state).is_err());
+    }
+
+    #[test]
+    fn interface_is_usable_in_between() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = Dwt::new(&mut mock.interface, &mock.component);
+
+        let address = {
+            let mut memory = dwt.interface().memory_interface(mock.component.ap).unwrap();
+            memory.write_word_32(0x2000_0000, 0x2000_0100).unwrap();
+            memory.read_word_32(0x2000_0000).unwrap()
+        };
+        dwt.enable_watchpoint(0, address, 4, WatchKind::Write)
+            .unwrap();
+
+        assert_eq!(mock.interface.read(0x20), 0x2000_0100);
+    }
+
+    #[test]
+    fn presence_is_detected() {
+        let mut mock = MockDwt::new(4);
+
+        let mut dwt = mock.dwt();
+        assert!(dwt.is_present().unwrap());
+
+        // Clear the part number in PIDR0
+        mock.interface.write(0xFE0, 0x00);
+        let mut dwt = mock.dwt();
+        assert!(!dwt.is_present().unwrap());
+    }
+
+    #[test]
+    fn sync_tap_round_trip() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable().unwrap();
+        assert_eq!(mock.interface.read(0x0) >> 10 & 0b11, 0b01);
+
+        let mut dwt = mock.dwt();
+        for tap in [
+            SyncTap::Disabled,
+            SyncTap::Cyccnt24,
+            SyncTap::Cyccnt26,
+            SyncTap::Cyccnt28,
+        ] {
+            let mut tap_read = None;
+            dwt.modify_ctrl(|ctrl| {
+                ctrl.set_sync_tap(tap);
+                tap_read = Some(ctrl.sync_tap());
+            })
+            .unwrap();
+            assert_eq!(tap_read, Some(tap));
+        }
+    }
+
+    #[test]
+    fn matched_mask_reports_matched_units() {
+        let mut mock = MockDwt::new(4);
+
+        // MATCHED of units 1 and 3
+        mock.interface.write(0x38, 1 << 24 | 0b0110);
+        mock.interface.write(0x58, 1 << 24 | 0b0101);
+
+        let mut dwt = mock.dwt();
+        assert_eq!(dwt.matched_mask().unwrap(), 0b1010);
+    }
+
+    #[test]
+    fn matched_watchpoint_is_reported() {
+        let mut words = [0; 16];
+        // CTRL.NUMCOMP
+        words[0] = 2 << 28;
+        // Unit 0 watches writes, unit 1 reads and matched.
+        words[8..12].copy_from_slice(&[0x2000_0000, 2, 0b0110, 0]);
+        words[12..16].copy_from_slice(&[0x2000_0100, 0, 1 << 24 | 0b0101, 0]);
+
+        let mut memory = MockMemory::new();
+        memory.add_word_range(CORTEX_M_DWT_BASE, &words);
+
+        assert_eq!(
+            cortex_m_matched_watchpoint(&mut memory).unwrap(),
+            Some(WatchpointConfig {
+                unit: 1,
+                address: 0x2000_0100,
+                length: 1,
+                kind: WatchKind::Read,
+            })
+        );
+    }
+
+    #[test]
+    fn enable_only_sets_implemented_features() {
+        let mut mock = MockDwt::new(4);
+
+        let mut dwt = mock.dwt();
+        dwt.enable().unwrap();
+        dwt.enable().unwrap();
+        // NUMCOMP, SYNCTAP and CYCCNTENA
+        assert_eq!(mock.interface.read(0x0), 4 << 28 | 0b01 << 10 | 1);
+
+        // NUMCOMP and NOCYCCNT
+        mock.interface.write(0x0, 4 << 28 | 1 << 25);
+        mock.dwt().enable().unwrap();
+        assert_eq!(mock.interface.read(0x0), 4 << 28 | 1 << 25);
+    }
+
+    #[test]
+    fn raw_comparator_registers() {
+        let mut mock = MockDwt::new(2);
+
+        mock.interface.write(0x30, 0x2000_0040);
+        mock.interface.write(0x34, 3);
+        mock.interface.write(0x38, 1 << 24 | 0b0110);
+
+        let mut dwt = mock.dwt();
+        assert_eq!(dwt.read_comp(1).unwrap(), 0x2000_0040);
+        assert_eq!(dwt.read_mask(1).unwrap(), 3);
+        assert_eq!(dwt.read_function(1).unwrap(), 1 << 24 | 0b0110);
+        assert!(matches!(dwt.read_function(2), Err(ArmError::OutOfBounds)));
+    }
+
+    #[test]
+    fn zero_length_watches_a_byte() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable_watchpoint(1, 0x2000_0003, 0, WatchKind::Read)
+            .unwrap();
+        assert!(matches!(
+            dwt.enable_watchpoint(2, 0x2000_0000, 3, WatchKind::Read),
+            Err(ArmError::InvalidWatchpointLength(3))
+        ));
+
+        assert_eq!(mock.interface.read(0x30), 0x2000_0003);
+        assert_eq!(mock.interface.read(0x34), 0);
+        assert_eq!(mock.interface.read(0x38), 0b0101);
+    }
+
+    #[test]
+    fn nth_access_watchpoint_counts_hits() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        assert!(dwt
+            .enable_nth_access_watchpoint(2, 0x2000_0004, 0, WatchKind::Write)
+            .is_err());
+
+        let mut watchpoint = dwt
+            .enable_nth_access_watchpoint(2, 0x2000_0004, 3, WatchKind::Write)
+            .unwrap();
+        assert_eq!(mock.interface.read(0x40), 0x2000_0004);
+        assert_eq!(mock.interface.read(0x44), 0);
+        assert_eq!(mock.interface.read(0x48), 0b0110);
+
+        assert!(!watchpoint.hit());
+        assert!(!watchpoint.hit());
+        assert_eq!(watchpoint.remaining(), 1);
+        assert!(watchpoint.hit());
+    }
+
+    #[test]
+    fn failed_commit_disables_comparator() {
+        let mut mock = MockDwt::new(4);
+
+        mock.interface.write(0x38, 0b0101);
+        mock.interface.fail_write(0x34);
+
+        let mut dwt = mock.dwt();
+        let result = dwt.enable_watchpoint(1, 0x2000_0000, 1, WatchKind::Write);
+        assert!(matches!(
+            result,
+            Err(ArmError::DwtComparator {
+                register: "DWT/MASK",
+                unit: 1,
+                ..
+            })
+        ));
+        assert_eq!(mock.interface.read(0x30), 0x2000_0000);
+        assert_eq!(mock.interface.read(0x38), 0);
+    }
+
+    #[test]
+    fn pc_sample_rate_sets_postcnt() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.set_pc_sample_rate(false, 9).unwrap();
+        assert!(dwt.set_pc_sample_rate(true, 16).is_err());
+
+        // NUMCOMP, POSTINIT and POSTPRESET
+        assert_eq!(mock.interface.read(0x0), 4 << 28 | 9 << 5 | 9 << 1);
+
+        // POSTINIT is kept while PCSAMPLENA is set.
+        mock.interface.write(0x0, 4 << 28 | 1 << 12);
+        let mut dwt = mock.dwt();
+        dwt.set_pc_sample_rate(true, 2).unwrap();
+        assert_eq!(
+            mock.interface.read(0x0),
+            4 << 28 | 1 << 12 | 1 << 9 | 2 << 1
+        );
+    }
+
+    #[test]
+    fn profiling_selects_closest_interval() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        let config = ProfilingConfig {
+            core_clock: 64_000_000,
+            sample_rate: 10_000,
+            sync_tap: SyncTap::Cyccnt24,
+        };
+        // 6400 cycles are requested, 6 * 1024 is the closest interval.
+        assert_eq!(dwt.configure_for_profiling(config).unwrap(), 10_416);
+        assert_eq!(
+            mock.interface.read(0x0),
+            4 << 28 | 1 << 12 | 1 << 10 | 1 << 9 | 5 << 5 | 5 << 1 | 1
+        );
+
+        // NOTRCPKT
+        mock.interface.write(0x0, 4 << 28 | 1 << 27);
+        let mut dwt = mock.dwt();
+        assert!(matches!(
+            dwt.configure_for_profiling(config),
+            Err(ArmError::ExtensionRequired(_))
+        ));
+        assert!(dwt
+            .configure_for_profiling(ProfilingConfig {
+                sample_rate: 0,
+                ..config
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn capabilities_display() {
+        let mut mock = MockDwt::new(4);
+
+        // NOCYCCNT
+        mock.interface.write(0x0, 4 << 28 | 1 << 25);
+
+        let capabilities = mock.dwt().capabilities().unwrap();
+        assert_eq!(
+            capabilities.to_string(),
+            "DWT info:
+  number of comparators available: 4
+  trace sampling support: true
+  compare match support: true
+  cyccnt support: false
+  performance counter support: true"
+        );
+    }
+
+    #[test]
+    fn rearm_requires_active_comparator() {
+        let mut mock = MockDwt::new(4);
+
+        mock.interface.write(0x38, 1 << 24 | 0b0110);
+
+        let mut dwt = mock.dwt();
+        assert!(dwt.rearm_watchpoint(1).unwrap());
+        assert!(dwt.rearm_watchpoint(2).is_err());
+    }
+
+    #[test]
+    fn masked_watchpoint_ignores_low_bits() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable_masked_watchpoint(2, 0x2000_1234, 8, WatchKind::Write)
+            .unwrap();
+        assert!(dwt
+            .enable_masked_watchpoint(1, 0x2000_0000, 32, WatchKind::Write)
+            .is_err());
+
+        assert_eq!(mock.interface.read(0x40), 0x2000_1200);
+        assert_eq!(mock.interface.read(0x44), 8);
+        assert_eq!(mock.interface.read(0x48), 0b0110);
+        assert_eq!(mock.interface.read(0x38), 0);
+    }
+
+    #[test]
+    fn watchpoint_length_is_limited_by_mask() {
+        let mut mock = MockDwt::new(4);
+        // MASK of unit 1 only implements 4 bits, so it ignores at most 15 address bits.
+        mock.interface.implement_bits(0x34, 0xf);
+
+        let mut dwt = mock.dwt();
+        dwt.enable_watchpoint(1, 0x2000_0000, 0x8000, WatchKind::Write)
+            .unwrap();
+        assert!(dwt
+            .enable_watchpoint(1, 0x2001_0000, 0x1_0000, WatchKind::Write)
+            .is_err());
+
+        assert_eq!(mock.interface.read(0x30), 0x2000_0000);
+        assert_eq!(mock.interface.read(0x34), 15);
+        assert_eq!(mock.interface.read(0x38), 0b0110);
+    }
+
+    #[test]
+    fn rounded_watchpoint_covers_region() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        assert_eq!(
+            dwt.enable_rounded_watchpoint(0, 0x2000_0000, 6, WatchKind::Read)
+                .unwrap(),
+            8
+        );
+        // The region crosses an 8 byte boundary, so a 16 byte block is needed.
+        assert_eq!(
+            dwt.enable_rounded_watchpoint(1, 0x2000_0006, 4, WatchKind::Read)
+                .unwrap(),
+            16
+        );
+        assert_eq!(
+            dwt.enable_rounded_watchpoint(2, 0x2000_0004, 4, WatchKind::Read)
+                .unwrap(),
+            4
+        );
+
+        assert_eq!(mock.interface.read(0x24), 3);
+        assert_eq!(mock.interface.read(0x30), 0x2000_0000);
+        assert_eq!(mock.interface.read(0x34), 4);
+        assert_eq!(mock.interface.read(0x40), 0x2000_0004);
+        assert_eq!(mock.interface.read(0x44), 2);
+    }
+
+    #[test]
+    fn value_match_support_is_probed() {
+        let mut mock = MockDwt::new(0);
+        assert!(!mock.dwt().supports_value_match().unwrap());
+
+        let mut mock = MockDwt::new(4);
+        mock.interface.write(0x28, 0b0110);
+
+        assert!(mock.dwt().supports_value_match().unwrap());
+        // The probed comparator is restored, the active one is left alone.
+        assert_eq!(mock.interface.read(0x28), 0b0110);
+        assert_eq!(mock.interface.read(0x38), 0);
+    }
+
+    #[cfg(feature = "dwt-stats")]
+    #[test]
+    fn transfer_stats_count_accesses() {
+        use super::DwtTransferStats;
+
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        dwt.enable_watchpoint(1, 0x2000_0000, 4, WatchKind::Write)
+            .unwrap();
+
+        // CTRL for the unit check and MASK to probe its size, which is restored, then CTRL
+        // again and COMP, MASK and FUNCTION are read and written.
+        assert_eq!(
+            dwt.transfer_stats(),
+            DwtTransferStats {
+                reads: 7,
+                writes: 5
+            }
+        );
+    }
+
+    #[test]
+    fn suspended_watchpoints_are_restored() {
+        let mut mock = MockDwt::new(4);
+
+        mock.interface.write(0x28, 0b0110);
+        mock.interface.write(0x38, 0b0010);
+        mock.interface.write(0x48, 1 << 10 | 0b0101);
+
+        let mut dwt = mock.dwt();
+        let mut guard = dwt.suspend_watchpoints().unwrap();
+        assert_eq!(guard.suspended_units(), [0, 2]);
+        assert_eq!(guard.dwt().read_function(0).unwrap(), 0);
+        assert_eq!(guard.dwt().read_function(1).unwrap(), 0b0010);
+        assert_eq!(guard.dwt().read_function(2).unwrap(), 1 << 10);
+        guard.restore().unwrap();
+
+        assert_eq!(mock.interface.read(0x28), 0b0110);
+        assert_eq!(mock.interface.read(0x48), 1 << 10 | 0b0101);
+
+        // If a comparator can't be disabled, the ones before it are restored.
+        mock.interface.fail_write(0x48);
+        let mut dwt = mock.dwt();
+        assert!(dwt.suspend_watchpoints().is_err());
+        assert_eq!(mock.interface.read(0x28), 0b0110);
+    }
+
+    #[test]
+    fn peripheral_window_is_watched() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+
+        assert_eq!(
+            dwt.watch_peripheral(0x4000_4400, 0x400, WatchKind::Write)
+                .unwrap(),
+            0
+        );
+        assert!(dwt
+            .watch_peripheral(0x4000_4400, 0x300, WatchKind::Write)
+            .is_err());
+        assert!(dwt
+            .watch_peripheral(0x4000_4200, 0x400, WatchKind::Write)
+            .is_err());
+
+        assert_eq!(mock.interface.read(0x20), 0x4000_4400);
+        assert_eq!(mock.interface.read(0x24), 10);
+        assert_eq!(mock.interface.read(0x28), 0b0110);
+        assert_eq!(mock.interface.read(0x38), 0);
+    }
+
+    #[test]
+    fn highest_first_allocation() {
+        let mut mock = MockDwt::new(4);
+        let mut dwt = mock.dwt();
+        dwt.set_allocation_strategy(AllocationStrategy::HighestFirst);
+
+        assert_eq!(
+            dwt.allocate_watchpoint(0x2000_0000, 4, WatchKind::Write)
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            dwt.enable_range_watchpoint(0x2000_0004, 0x2000_0010, WatchKind::Read)
+                .unwrap(),
+            [2, 1]
+        );
+        assert_eq!(dwt.allocate_instruction_watchpoint(0x1000).unwrap(), 0);
+    }
+
+    #[test]
+    fn unit_capabilities_are_probed() {
+        let mut mock = MockDwt::new(4);
+        mock.interface.write(0x28, 0b0110);
+
+        let mut dwt = mock.dwt();
+        assert_eq!(
+            dwt.unit_capabilities(1).unwrap(),
+            UnitCapabilities {
+                value_match: true,
+                cycle_match: true,
+                max_mask_bits: 0x1f,
+            }
+        );
+        assert!(dwt.unit_capabilities(0).is_err());
+        assert!(dwt.unit_capabilities(4).is_err());
+
+        // The probed registers are restored.
+        assert_eq!(mock.interface.read(0x38), 0);
+        assert_eq!(mock.interface.read(0x34), 0);
+
+        // Without a cycle counter, no comparator can match it.
+        mock.interface
+            .write(0x0, mock.interface.read(0x0) | 1 << 25);
+        let mut dwt = mock.dwt();
+        assert!(!dwt.unit_capabilities(1).unwrap().cycle_match);
+    }
+
+    #[test]
+    fn reserved_cycle_counter_is_kept() {
+        let mut mock = MockDwt::new(4);
+        mock.interface.write(0x0, mock.interface.read(0x0) | 1);
+        mock.interface.write(0x4, 1234);
+
+        let mut dwt = mock.dwt();
+        let reservation = dwt.reserve_cyccnt().unwrap();
+        assert!(dwt.reserve_cyccnt().is_err());
+        assert!(dwt.reset_cycle_count().is_err());
+        dwt.disable_all().unwrap();
+        assert_eq!(dwt.read_cycle_count().unwrap(), 1234);
+        let reservations = dwt.cyccnt_reservations();
+        let mut other = mock.dwt();
+        other.set_cyccnt_reservations(reservations.clone());
+        assert!(CycleCounter::new(other).is_err());
+
+        reservation.release();
+        let mut dwt = mock.dwt();
+        dwt.set_cyccnt_reservations(reservations);
+        dwt.reset_cycle_count().unwrap();
+        assert_eq!(mock.interface.read(0x0) & 1, 1);
+        assert_eq!(mock.interface.read(0x4), 0);
+    }
+
+    #[test]
+    fn cycle_counter_is_not_restarted_while_reserved() {
+        let mut mock = MockDwt::new(4);
+
+        let mut counter = CycleCounter::new(mock.dwt()).unwrap();
+        let reservation = counter.dwt.reserve_cyccnt().unwrap();
+        assert!(counter.restart().is_err());
+
+        reservation.release();
+        counter.restart().unwrap();
+    }
+}
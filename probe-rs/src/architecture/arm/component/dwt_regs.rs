@@ -0,0 +1,348 @@
+//! Register definitions of the DWT (data watchpoint and trace) unit.
+//!
+//! The definitions only describe the encoding of the registers, they don't depend on the
+//! probe interface. See [`Dwt`](super::Dwt) for accessing the registers of a target.
+//!
+//! This module only uses `core` and the `bitfield` crate, so that `no_std` tooling can include
+//! the file to reuse the encodings. [`MemoryMappedRegister`](crate::MemoryMappedRegister), which
+//! depends on the `std` only dependencies of probe-rs, is implemented by the `dwt` module.
+#![deny(clippy::std_instead_of_core, clippy::std_instead_of_alloc)]
+
+/// Defines the DWT register `$name` located at `$offset` from the base address of the DWT.
+///
+/// This is the part of [`memory_mapped_bitfield_register!`](crate::memory_mapped_bitfield_register)
+/// which does not depend on the rest of probe-rs.
+macro_rules! dwt_register {
+    ($(#[$outer:meta])* pub struct $name:ident(u32); $offset:expr, $reg_name:expr, $($rest:tt)*) => {
+        bitfield::bitfield! {
+            $(#[$outer])*
+            #[derive(Copy, Clone)]
+            pub struct $name(u32);
+            impl Debug;
+            $($rest)*
+        }
+
+        impl $name {
+            /// The offset of the register from the base address of the DWT.
+            pub const ADDRESS_OFFSET: u64 = $offset;
+            /// The name of the register.
+            pub const NAME: &'static str = $reg_name;
+        }
+
+        impl From<$name> for u32 {
+            fn from(register: $name) -> Self {
+                register.0
+            }
+        }
+
+        impl From<u32> for $name {
+            fn from(value: u32) -> Self {
+                Self(value)
+            }
+        }
+    };
+}
+
+/// The CYCCNT bit which drives the generation of synchronization packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncTap {
+    /// No synchronization packets are generated.
+    Disabled,
+    /// Synchronization counter tap at CYCCNT\[24\].
+    Cyccnt24,
+    /// Synchronization counter tap at CYCCNT\[26\].
+    Cyccnt26,
+    /// Synchronization counter tap at CYCCNT\[28\].
+    Cyccnt28,
+}
+
+impl From<SyncTap> for u8 {
+    fn from(tap: SyncTap) -> Self {
+        match tap {
+            SyncTap::Disabled => 0b00,
+            SyncTap::Cyccnt24 => 0b01,
+            SyncTap::Cyccnt26 => 0b10,
+            SyncTap::Cyccnt28 => 0b11,
+        }
+    }
+}
+
+dwt_register! {
+    /// The control register of the DWT.
+    pub struct Ctrl(u32);
+    0x00, "DWT/CTRL",
+    /// The number of comparators implemented.
+    pub u8, numcomp, _: 31, 28;
+    /// Trace sampling and exception tracing are not supported.
+    pub notrcpkt, _: 27;
+    /// External match signals (`CMPMATCH`) are not supported.
+    pub noexttrig, _: 26;
+    /// The cycle counter is not supported.
+    pub nocyccnt, _: 25;
+    /// The profiling counters are not supported.
+    pub noprfcnt, _: 24;
+    /// Enables the POSTCNT underflow event counter packets.
+    pub cycevtena, set_cycevtena: 22;
+    /// Enables the folded instruction count overflow event.
+    pub foldevtena, set_foldevtena: 21;
+    /// Enables the LSU count overflow event.
+    pub lsuevtena, set_lsuevtena: 20;
+    /// Enables the sleep count overflow event.
+    pub sleepevtena, set_sleepevtena: 19;
+    /// Enables the exception overhead count overflow event.
+    pub excevtena, set_excevtena: 18;
+    /// Enables the CPI count overflow event.
+    pub cpievtena, set_cpievtena: 17;
+    /// Enables the generation of exception trace.
+    pub exctrcena, set_exctrcena: 16;
+    /// Enables the generation of periodic PC sample packets.
+    pub pcsamplena, set_pcsamplena: 12;
+    /// The raw SYNCTAP field, see [`Ctrl::sync_tap`].
+    pub u8, synctap, set_synctap: 11, 10;
+    /// Selects CYCCNT\[10\] instead of CYCCNT\[6\] as the tap for the POSTCNT timer.
+    pub cyctap, set_cyctap: 9;
+    /// The initial value of the POSTCNT counter.
+    pub u8, postinit, set_postinit: 8, 5;
+    /// The reload value of the POSTCNT counter.
+    pub postpreset, set_postpreset: 4, 1;
+    /// Enables the cycle counter.
+    pub cyccntena, set_cyccntena: 0;
+}
+
+impl Ctrl {
+    /// Returns the CYCCNT bit which drives the generation of synchronization packets.
+    pub fn sync_tap(&self) -> SyncTap {
+        match self.synctap() {
+            0b00 => SyncTap::Disabled,
+            0b01 => SyncTap::Cyccnt24,
+            0b10 => SyncTap::Cyccnt26,
+            _ => SyncTap::Cyccnt28,
+        }
+    }
+
+    /// Sets the CYCCNT bit which drives the generation of synchronization packets.
+    pub fn set_sync_tap(&mut self, tap: SyncTap) {
+        self.set_synctap(tap.into());
+    }
+}
+
+dwt_register! {
+    /// The cycle count register.
+    pub struct Cyccnt(u32);
+    0x04, "DWT/CYCCNT",
+}
+
+dwt_register! {
+    /// The CPI count register.
+    pub struct Cpicnt(u32);
+    0x08, "DWT/CPICNT",
+    /// The additional cycles required to execute instructions.
+    pub u8, cpicnt, set_cpicnt: 7, 0;
+}
+
+dwt_register! {
+    /// The exception overhead count register.
+    pub struct Exccnt(u32);
+    0x0C, "DWT/EXCCNT",
+    /// The cycles spent on exception entry and return.
+    pub u8, exccnt, set_exccnt: 7, 0;
+}
+
+dwt_register! {
+    /// The sleep count register.
+    pub struct Sleepcnt(u32);
+    0x10, "DWT/SLEEPCNT",
+    /// The cycles spent sleeping.
+    pub u8, sleepcnt, set_sleepcnt: 7, 0;
+}
+
+dwt_register! {
+    /// The LSU count register.
+    pub struct Lsucnt(u32);
+    0x14, "DWT/LSUCNT",
+    /// The additional cycles required to execute load and store instructions.
+    pub u8, lsucnt, set_lsucnt: 7, 0;
+}
+
+dwt_register! {
+    /// The folded instruction count register.
+    pub struct Foldcnt(u32);
+    0x18, "DWT/FOLDCNT",
+    /// The number of folded instructions.
+    pub u8, foldcnt, set_foldcnt: 7, 0;
+}
+
+dwt_register! {
+    /// The comparator register of a DWT unit.
+    pub struct Comp(u32);
+    0x20, "DWT/COMP",
+    /// The value to compare against, an address or a data value.
+    pub u32, comp, set_comp: 31, 0;
+}
+
+dwt_register! {
+    /// The mask register of a DWT unit.
+    pub struct Mask(u32);
+    0x24, "DWT/MASK",
+    /// The number of low address bits ignored by the comparison.
+    pub u32, mask, set_mask: 4, 0;
+}
+
+dwt_register! {
+    /// The function register of a DWT unit.
+    pub struct Function(u32);
+    0x28, "DWT/FUNCTION",
+    /// The comparator matched since the register was last read. Reading clears this bit.
+    pub matched, _: 24;
+    /// The second comparator linked for data value matching.
+    pub u8, datavaddr1, set_datavaddr1: 19, 16;
+    /// The first comparator linked for data value matching.
+    pub u8, datavaddr0, set_datavaddr0: 15, 12;
+    /// The size of the data value to match.
+    ///
+    /// 00 Byte.
+    /// 01 Halfword.
+    /// 10 Word.
+    pub u8, datavsize, set_datavsize: 11, 10;
+    /// A second linked comparator is supported.
+    pub lnk1ena, _: 9;
+    /// Match data values instead of addresses.
+    pub datavmatch, set_datavmatch: 8;
+    /// Match the cycle counter instead of addresses. Only supported by comparator 0.
+    pub cycmatch, set_cycmatch: 7;
+    /// Emit address offsets instead of data addresses in data trace packets.
+    pub emitrange, set_emitrange: 5;
+    /// The function of the comparator, `0` disables it.
+    pub function, set_function: 3, 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comp, Ctrl, Function, Mask};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Conversion of raw field values to the types used by the register accessors.
+    trait FieldValue: PartialEq {
+        fn from_raw(raw: u32) -> Self;
+    }
+
+    impl FieldValue for bool {
+        fn from_raw(raw: u32) -> Self {
+            raw != 0
+        }
+    }
+
+    impl FieldValue for u8 {
+        fn from_raw(raw: u32) -> Self {
+            raw as u8
+        }
+    }
+
+    impl FieldValue for u32 {
+        fn from_raw(raw: u32) -> Self {
+            raw
+        }
+    }
+
+    fn matches_raw<T: FieldValue>(value: T, raw: u32) -> bool {
+        value == T::from_raw(raw)
+    }
+
+    /// Returns pairs of register and field values to check a field of `width` bits with.
+    fn field_samples(width: u32) -> Vec<(u32, u32)> {
+        let max = u32::MAX >> (32 - width);
+        let mut rng = StdRng::seed_from_u64(0xD317);
+
+        let mut samples = vec![(0, 0), (0, max), (u32::MAX, 0), (u32::MAX, max)];
+        samples.extend((0..256).map(|_| (rng.gen(), rng.gen::<u32>() & max)));
+        samples
+    }
+
+    /// Checks that a field of register `$reg` occupies exactly the bits `$msb` to `$lsb`, and
+    /// returns the mask of these bits.
+    ///
+    /// The getter `$get` has to extract the field from any register value. If a setter `$set` is
+    /// given, it has to store any field value without touching the other bits.
+    macro_rules! check_field {
+        ($reg:ident, $get:ident, $set:ident, $msb:literal, $lsb:literal) => {{
+            let mask = check_field!($reg, $get, $msb, $lsb);
+            for (base, value) in field_samples($msb - $lsb + 1) {
+                let mut register = $reg::from(base);
+                register.$set(FieldValue::from_raw(value));
+                assert_eq!(
+                    u32::from(register),
+                    base & !mask | value << $lsb,
+                    concat!(stringify!($reg), "::", stringify!($set))
+                );
+            }
+            mask
+        }};
+        ($reg:ident, $get:ident, $msb:literal, $lsb:literal) => {{
+            let mask = (u32::MAX >> (31 - $msb + $lsb)) << $lsb;
+            for (base, value) in field_samples($msb - $lsb + 1) {
+                let register = $reg::from(base & !mask | value << $lsb);
+                assert!(
+                    matches_raw(register.$get(), value),
+                    concat!(stringify!($reg), "::", stringify!($get))
+                );
+            }
+            mask
+        }};
+    }
+
+    /// Asserts that the fields with the bit masks `masks` do not overlap.
+    fn assert_disjoint(register: &str, masks: &[u32]) {
+        let mut used = 0;
+        for mask in masks {
+            assert_eq!(used & mask, 0, "Overlapping fields in {register}");
+            used |= mask;
+        }
+    }
+
+    #[test]
+    fn ctrl_fields_round_trip() {
+        let masks = [
+            check_field!(Ctrl, numcomp, 31, 28),
+            check_field!(Ctrl, notrcpkt, 27, 27),
+            check_field!(Ctrl, noexttrig, 26, 26),
+            check_field!(Ctrl, nocyccnt, 25, 25),
+            check_field!(Ctrl, noprfcnt, 24, 24),
+            check_field!(Ctrl, cycevtena, set_cycevtena, 22, 22),
+            check_field!(Ctrl, foldevtena, set_foldevtena, 21, 21),
+            check_field!(Ctrl, lsuevtena, set_lsuevtena, 20, 20),
+            check_field!(Ctrl, sleepevtena, set_sleepevtena, 19, 19),
+            check_field!(Ctrl, excevtena, set_excevtena, 18, 18),
+            check_field!(Ctrl, cpievtena, set_cpievtena, 17, 17),
+            check_field!(Ctrl, exctrcena, set_exctrcena, 16, 16),
+            check_field!(Ctrl, pcsamplena, set_pcsamplena, 12, 12),
+            check_field!(Ctrl, synctap, set_synctap, 11, 10),
+            check_field!(Ctrl, cyctap, set_cyctap, 9, 9),
+            check_field!(Ctrl, postinit, set_postinit, 8, 5),
+            check_field!(Ctrl, postpreset, set_postpreset, 4, 1),
+            check_field!(Ctrl, cyccntena, set_cyccntena, 0, 0),
+        ];
+        assert_disjoint("DWT/CTRL", &masks);
+    }
+
+    #[test]
+    fn function_fields_round_trip() {
+        let masks = [
+            check_field!(Function, matched, 24, 24),
+            check_field!(Function, datavaddr1, set_datavaddr1, 19, 16),
+            check_field!(Function, datavaddr0, set_datavaddr0, 15, 12),
+            check_field!(Function, datavsize, set_datavsize, 11, 10),
+            check_field!(Function, lnk1ena, 9, 9),
+            check_field!(Function, datavmatch, set_datavmatch, 8, 8),
+            check_field!(Function, cycmatch, set_cycmatch, 7, 7),
+            check_field!(Function, emitrange, set_emitrange, 5, 5),
+            check_field!(Function, function, set_function, 3, 0),
+        ];
+        assert_disjoint("DWT/FUNCTION", &masks);
+    }
+
+    #[test]
+    fn comp_and_mask_fields_round_trip() {
+        check_field!(Comp, comp, set_comp, 31, 0);
+        check_field!(Mask, mask, set_mask, 4, 0);
+    }
+}
@@ -82,6 +82,19 @@ impl<'a> Itm<'a> {
 
         Ok(())
     }
+
+    /// Enable the stimulus ports set in the `ports` bit mask, disabling all others.
+    ///
+    /// Bit `n` of `ports` corresponds to stimulus port `n`. [`Itm::tx_enable`] enables all ports.
+    pub fn set_stimulus_ports(&mut self, ports: u32) -> Result<(), Error> {
+        self.component.write_reg(
+            self.interface,
+            register::ITM_TER::ADDRESS_OFFSET as u32,
+            register::ITM_TER::from(ports).into(),
+        )?;
+
+        Ok(())
+    }
 }
 
 mod register {
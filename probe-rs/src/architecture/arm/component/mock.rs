@@ -0,0 +1,304 @@
+//! A mocked ARM interface for testing CoreSight components without hardware.
+
+use std::collections::{HashMap, HashSet};
+
+use super::Dwt;
+use crate::architecture::arm::{
+    ap::{GenericAp, MemoryAp},
+    communication_interface::{Initialized, SwdSequence},
+    memory::{adi_v5_memory_interface::ArmProbe, Component, CoresightComponent},
+    ApAddress, ApInformation, ArmChipInfo, ArmCommunicationInterface, ArmError, ArmProbeInterface,
+    DapAccess, DpAddress, SwoAccess, SwoConfig,
+};
+use crate::{DebugProbeError, Probe};
+
+/// Base address of the mocked component.
+pub(crate) const COMPONENT_BASE: u64 = 0xE000_1000;
+
+/// An ARM interface which backs all memory accesses with a sparse register file.
+///
+/// Registers which have not been written read as zero.
+#[derive(Debug, Default)]
+pub(crate) struct MockArmInterface {
+    /// The current register values, keyed by absolute address.
+    pub registers: HashMap<u64, u32>,
+    /// The absolute addresses of registers for which writes fail.
+    pub failing_writes: HashSet<u64>,
+    /// The implemented bits of registers, keyed by absolute address. Writes to other bits
+    /// of these registers are ignored.
+    pub implemented_bits: HashMap<u64, u32>,
+}
+
+impl MockArmInterface {
+    /// Creates an interface with a Cortex-M3 DWT implementing `numcomp` comparators.
+    pub(crate) fn with_dwt(numcomp: u8) -> Self {
+        Self {
+            registers: dwt_registers(numcomp).into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the component located at [`COMPONENT_BASE`].
+    pub(crate) fn component(&mut self) -> CoresightComponent {
+        let ap = MemoryAp::new(ApAddress {
+            dp: DpAddress::Default,
+            ap: 0,
+        });
+
+        let mut memory = MockMemory {
+            registers: &mut self.registers,
+            failing_writes: &self.failing_writes,
+            implemented_bits: &self.implemented_bits,
+        };
+        let component = Component::try_parse(&mut memory, COMPONENT_BASE).unwrap();
+
+        CoresightComponent::new(component, ap)
+    }
+
+    /// Reads a register at `offset` relative to [`COMPONENT_BASE`].
+    pub(crate) fn read(&self, offset: u32) -> u32 {
+        self.registers
+            .get(&(COMPONENT_BASE + offset as u64))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Writes a register at `offset` relative to [`COMPONENT_BASE`].
+    pub(crate) fn write(&mut self, offset: u32, value: u32) {
+        self.registers.insert(COMPONENT_BASE + offset as u64, value);
+    }
+
+    /// Makes writes to the register at `offset` relative to [`COMPONENT_BASE`] fail.
+    pub(crate) fn fail_write(&mut self, offset: u32) {
+        self.failing_writes.insert(COMPONENT_BASE + offset as u64);
+    }
+
+    /// Makes the register at `offset` relative to [`COMPONENT_BASE`] implement only `bits`,
+    /// so writes to its other bits are ignored.
+    pub(crate) fn implement_bits(&mut self, offset: u32, bits: u32) {
+        self.implemented_bits
+            .insert(COMPONENT_BASE + offset as u64, bits);
+    }
+}
+
+/// A mocked Cortex-M3 DWT, made of the interface it is accessed through and its component.
+pub(crate) struct MockDwt {
+    pub interface: MockArmInterface,
+    pub component: CoresightComponent,
+}
+
+impl MockDwt {
+    /// Creates a DWT implementing `numcomp` comparators.
+    pub(crate) fn new(numcomp: u8) -> Self {
+        let mut interface = MockArmInterface::with_dwt(numcomp);
+        let component = interface.component();
+
+        Self {
+            interface,
+            component,
+        }
+    }
+
+    /// Returns a [`Dwt`] accessing the mocked DWT.
+    pub(crate) fn dwt(&mut self) -> Dwt<'_> {
+        Dwt::new(&mut self.interface, &self.component)
+    }
+}
+
+/// Returns the registers of a Cortex-M3 DWT at [`COMPONENT_BASE`] implementing `numcomp`
+/// comparators, keyed by absolute address.
+pub(crate) fn dwt_registers(numcomp: u8) -> Vec<(u64, u32)> {
+    let register = |offset: u64, value| (COMPONENT_BASE + offset, value);
+    let mut registers = Vec::new();
+
+    // CIDR0..3, class 0xE
+    for (i, value) in [0x0D, 0xE0, 0x05, 0xB1].into_iter().enumerate() {
+        registers.push(register(0xFF0 + 4 * i as u64, value));
+    }
+    // PIDR0..3 and PIDR4, designer ARM Ltd, part 0x002
+    for (i, value) in [0x02, 0xB0, 0x0B, 0x00].into_iter().enumerate() {
+        registers.push(register(0xFE0 + 4 * i as u64, value));
+    }
+    registers.push(register(0xFD0, 0x04));
+
+    // DWT/CTRL.NUMCOMP
+    registers.push(register(0x0, (numcomp as u32) << 28));
+
+    registers
+}
+
+struct MockMemory<'a> {
+    registers: &'a mut HashMap<u64, u32>,
+    failing_writes: &'a HashSet<u64>,
+    implemented_bits: &'a HashMap<u64, u32>,
+}
+
+impl SwdSequence for MockMemory<'_> {
+    fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
+        unimplemented!()
+    }
+
+    fn swj_pins(
+        &mut self,
+        _pin_out: u32,
+        _pin_select: u32,
+        _pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        unimplemented!()
+    }
+}
+
+impl ArmProbe for MockMemory<'_> {
+    fn read_8(&mut self, _address: u64, _data: &mut [u8]) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+        for (i, value) in data.iter_mut().enumerate() {
+            let address = address + 4 * i as u64;
+            *value = self.registers.get(&address).copied().unwrap_or(0);
+        }
+
+        Ok(())
+    }
+
+    fn read_64(&mut self, _address: u64, _data: &mut [u64]) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn write_8(&mut self, _address: u64, _data: &[u8]) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError> {
+        for (i, value) in data.iter().enumerate() {
+            let address = address + 4 * i as u64;
+            if self.failing_writes.contains(&address) {
+                return Err(ArmError::Other(anyhow::anyhow!(
+                    "Write to {address:#010x} failed"
+                )));
+            }
+            let bits = self.implemented_bits.get(&address).unwrap_or(&u32::MAX);
+            self.registers.insert(address, *value & bits);
+        }
+
+        Ok(())
+    }
+
+    fn write_64(&mut self, _address: u64, _data: &[u64]) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn flush(&mut self) -> Result<(), ArmError> {
+        Ok(())
+    }
+
+    fn supports_native_64bit_access(&mut self) -> bool {
+        false
+    }
+
+    fn supports_8bit_transfers(&self) -> Result<bool, ArmError> {
+        Ok(false)
+    }
+
+    fn ap(&mut self) -> MemoryAp {
+        unimplemented!()
+    }
+
+    fn get_arm_communication_interface(
+        &mut self,
+    ) -> Result<&mut ArmCommunicationInterface<Initialized>, DebugProbeError> {
+        Err(DebugProbeError::NotImplemented(
+            "get_arm_communication_interface",
+        ))
+    }
+}
+
+impl SwdSequence for MockArmInterface {
+    fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
+        unimplemented!()
+    }
+
+    fn swj_pins(
+        &mut self,
+        _pin_out: u32,
+        _pin_select: u32,
+        _pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        unimplemented!()
+    }
+}
+
+impl DapAccess for MockArmInterface {
+    fn read_raw_dp_register(&mut self, _dp: DpAddress, _addr: u8) -> Result<u32, ArmError> {
+        unimplemented!()
+    }
+
+    fn write_raw_dp_register(
+        &mut self,
+        _dp: DpAddress,
+        _addr: u8,
+        _value: u32,
+    ) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn read_raw_ap_register(&mut self, _ap: ApAddress, _addr: u8) -> Result<u32, ArmError> {
+        unimplemented!()
+    }
+
+    fn write_raw_ap_register(
+        &mut self,
+        _ap: ApAddress,
+        _addr: u8,
+        _value: u32,
+    ) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+}
+
+impl SwoAccess for MockArmInterface {
+    fn enable_swo(&mut self, _config: &SwoConfig) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn disable_swo(&mut self) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn read_swo_timeout(&mut self, _timeout: std::time::Duration) -> Result<Vec<u8>, ArmError> {
+        unimplemented!()
+    }
+}
+
+impl ArmProbeInterface for MockArmInterface {
+    fn memory_interface(
+        &mut self,
+        _access_port: MemoryAp,
+    ) -> Result<Box<dyn ArmProbe + '_>, ArmError> {
+        Ok(Box::new(MockMemory {
+            registers: &mut self.registers,
+            failing_writes: &self.failing_writes,
+            implemented_bits: &self.implemented_bits,
+        }))
+    }
+
+    fn ap_information(&mut self, _access_port: GenericAp) -> Result<&ApInformation, ArmError> {
+        unimplemented!()
+    }
+
+    fn num_access_ports(&mut self, _dp: DpAddress) -> Result<usize, ArmError> {
+        Ok(1)
+    }
+
+    fn read_chip_info_from_rom_table(
+        &mut self,
+        _dp: DpAddress,
+    ) -> Result<Option<ArmChipInfo>, ArmError> {
+        Ok(None)
+    }
+
+    fn close(self: Box<Self>) -> Probe {
+        unimplemented!()
+    }
+}
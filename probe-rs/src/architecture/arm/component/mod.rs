@@ -1,7 +1,10 @@
 //! Types and functions for interacting with CoreSight Components
 
 mod dwt;
+pub mod dwt_regs;
 mod itm;
+#[cfg(test)]
+mod mock;
 mod scs;
 mod swo;
 mod tmc;
@@ -18,7 +21,20 @@ use crate::architecture::arm::{ArmProbeInterface, SwoConfig, SwoMode};
 use crate::{Core, Error, MemoryInterface, MemoryMappedRegister};
 
 pub use self::itm::Itm;
-pub use dwt::Dwt;
+#[cfg(feature = "dwt-stats")]
+pub use dwt::DwtTransferStats;
+pub(crate) use dwt::{
+    cortex_m_free_comparators, cortex_m_matched_watchpoint, cortex_m_watchpoints,
+};
+pub use dwt::{
+    AllocationStrategy, Comparator, ComparatorState, CyccntReservation, CyccntReservations,
+    CycleCounter, Dwt, DwtCapabilities, DwtState, EmitOptions, NthAccessWatchpoint,
+    ProfilingConfig, SuspendGuard, UnitCapabilities, WatchKind, WatchSize, WatchpointBuilder,
+    WatchpointConfig,
+};
+pub use dwt_regs::{Ctrl as DwtCtrl, SyncTap};
+#[cfg(test)]
+pub(crate) use mock::{dwt_registers, COMPONENT_BASE};
 pub use scs::Scs;
 pub use swo::Swo;
 pub use tmc::TraceMemoryController;
@@ -41,6 +57,15 @@ pub enum TraceSink {
     TraceMemory,
 }
 
+/// Identifies a watchpoint configured on the DWT.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WatchpointRef {
+    /// The watchpoint configured on the DWT unit with this index.
+    Unit(usize),
+    /// The watchpoint configured on this address.
+    Address(u32),
+}
+
 /// An error when operating a core ROM table component occurred.
 #[derive(thiserror::Error, Debug)]
 pub enum ComponentError {
@@ -373,6 +398,127 @@ pub fn remove_swv_data_trace(
     dwt.disable_data_trace(unit)
 }
 
+/// Configures a free DWT unit as a watchpoint and returns the index of the unit used.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument. The unit is
+/// chosen according to `strategy`.
+pub fn add_watchpoint(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    address: u32,
+    length: u32,
+    kind: WatchKind,
+    strategy: AllocationStrategy,
+) -> Result<usize, ArmError> {
+    let component = find_component(components, PeripheralType::Dwt)?;
+    enable_dwt(interface, component)?;
+
+    let mut dwt = Dwt::new(interface, component);
+    dwt.set_allocation_strategy(strategy);
+    dwt.allocate_watchpoint(address, length, kind)
+}
+
+/// Configures a free DWT unit to match instruction fetches from `address` and returns the index
+/// of the unit used.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument. The unit is
+/// chosen according to `strategy`.
+pub fn add_instruction_watchpoint(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    address: u32,
+    strategy: AllocationStrategy,
+) -> Result<usize, ArmError> {
+    let component = find_component(components, PeripheralType::Dwt)?;
+    enable_dwt(interface, component)?;
+
+    let mut dwt = Dwt::new(interface, component);
+    dwt.set_allocation_strategy(strategy);
+    dwt.allocate_instruction_watchpoint(address)
+}
+
+/// Sets TRCENA in DEMCR, without which the DWT `component` does not operate.
+///
+/// DEMCR is accessed through the AP of the DWT, so that it is set for the core the DWT belongs to.
+fn enable_dwt(
+    interface: &mut dyn ArmProbeInterface,
+    component: &CoresightComponent,
+) -> Result<(), ArmError> {
+    let mut memory = interface.memory_interface(component.ap)?;
+    let mut demcr = Demcr(memory.read_word_32(Demcr::get_mmio_address())?);
+    if !demcr.dwtena() {
+        demcr.set_dwtena(true);
+        memory.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
+    }
+
+    Ok(())
+}
+
+/// Returns the number of comparators of the DWT, or 0 if there is no DWT.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument. A DWT listed
+/// in the ROM table which doesn't identify as one, see [`Dwt::is_present`], is not considered.
+pub fn watchpoint_units(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<usize, ArmError> {
+    let Ok(component) = find_component(components, PeripheralType::Dwt) else {
+        return Ok(0);
+    };
+
+    let mut dwt = Dwt::new(interface, component);
+    if !dwt.is_present()? {
+        return Ok(0);
+    }
+
+    Ok(dwt.comparator_count()? as usize)
+}
+
+/// Returns the number of DWT units which are available for watchpoints.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub fn available_watchpoints(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<usize, ArmError> {
+    let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+    dwt.free_comparator_count()
+}
+
+/// Disables the watchpoint identified by `watchpoint`.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub fn remove_watchpoint(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    watchpoint: WatchpointRef,
+) -> Result<(), ArmError> {
+    let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+
+    let unit = match watchpoint {
+        WatchpointRef::Unit(unit) => unit,
+        WatchpointRef::Address(address) => dwt.find_watchpoint(address)?.ok_or_else(|| {
+            ArmError::Other(anyhow::anyhow!(
+                "No watchpoint is set on address {address:#010x}"
+            ))
+        })?,
+    };
+
+    dwt.disable_watchpoint(unit)
+}
+
+/// Disables all comparators and counters of the DWT, and enables the DWT again.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub fn reset_dwt(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<(), ArmError> {
+    let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+    dwt.disable_all()?;
+    dwt.enable()
+}
+
 /// Sets TRCENA in DEMCR to begin trace generation.
 pub fn enable_tracing(core: &mut Core) -> Result<(), Error> {
     let mut demcr = Demcr(core.read_word_32(Demcr::get_mmio_address())?);
@@ -388,3 +534,132 @@ pub fn disable_swv(core: &mut Core) -> Result<(), Error> {
     core.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockArmInterface;
+    use super::*;
+
+    #[test]
+    fn remove_watchpoint_leaves_other_units_active() {
+        let mut interface = MockArmInterface::with_dwt(4);
+        let components = vec![interface.component()];
+
+        let first = add_watchpoint(
+            &mut interface,
+            &components,
+            0x2000_0000,
+            4,
+            WatchKind::Write,
+            AllocationStrategy::LowestFirst,
+        )
+        .unwrap();
+        let second = add_watchpoint(
+            &mut interface,
+            &components,
+            0x2000_0100,
+            4,
+            WatchKind::Read,
+            AllocationStrategy::LowestFirst,
+        )
+        .unwrap();
+        assert_eq!((first, second), (0, 1));
+
+        remove_watchpoint(&mut interface, &components, WatchpointRef::Unit(second)).unwrap();
+
+        // COMP and FUNCTION of unit 0 are untouched
+        assert_eq!(interface.read(0x20), 0x2000_0000);
+        assert_eq!(interface.read(0x28) & 0xF, 0b0110);
+        // FUNCTION of unit 1 is disabled
+        assert_eq!(interface.read(0x38) & 0xF, 0);
+    }
+
+    #[test]
+    fn read_and_write_watchpoints_use_separate_units() {
+        let mut interface = MockArmInterface::with_dwt(4);
+        let components = vec![interface.component()];
+
+        let read = add_watchpoint(
+            &mut interface,
+            &components,
+            0x2000_0000,
+            4,
+            WatchKind::Read,
+            AllocationStrategy::LowestFirst,
+        )
+        .unwrap();
+        let write = add_watchpoint(
+            &mut interface,
+            &components,
+            0x2000_0000,
+            4,
+            WatchKind::Write,
+            AllocationStrategy::LowestFirst,
+        )
+        .unwrap();
+        assert_eq!((read, write), (0, 1));
+        assert_eq!(interface.read(0x28) & 0xF, 0b0101);
+        assert_eq!(interface.read(0x38) & 0xF, 0b0110);
+
+        // MATCHED of the write watchpoint only
+        let function = interface.read(0x38);
+        interface.write(0x38, function | 1 << 24);
+
+        let mut dwt = Dwt::new(&mut interface, &components[0]);
+        assert!(!dwt.watchpoint_matched(read).unwrap());
+        assert!(dwt.watchpoint_matched(write).unwrap());
+    }
+
+    #[test]
+    fn remove_watchpoint_by_address() {
+        let mut interface = MockArmInterface::with_dwt(4);
+        let components = vec![interface.component()];
+
+        add_watchpoint(
+            &mut interface,
+            &components,
+            0x2000_0000,
+            4,
+            WatchKind::Write,
+            AllocationStrategy::LowestFirst,
+        )
+        .unwrap();
+        add_watchpoint(
+            &mut interface,
+            &components,
+            0x2000_0100,
+            4,
+            WatchKind::Read,
+            AllocationStrategy::LowestFirst,
+        )
+        .unwrap();
+
+        remove_watchpoint(
+            &mut interface,
+            &components,
+            WatchpointRef::Address(0x2000_0100),
+        )
+        .unwrap();
+        assert!(remove_watchpoint(
+            &mut interface,
+            &components,
+            WatchpointRef::Address(0x2000_0200)
+        )
+        .is_err());
+
+        assert_eq!(interface.read(0x28) & 0xF, 0b0110);
+        assert_eq!(interface.read(0x38) & 0xF, 0);
+    }
+
+    #[test]
+    fn missing_dwt_has_no_watchpoint_units() {
+        let mut interface = MockArmInterface::with_dwt(4);
+        let components = vec![interface.component()];
+        assert_eq!(watchpoint_units(&mut interface, &components).unwrap(), 4);
+        assert_eq!(watchpoint_units(&mut interface, &[]).unwrap(), 0);
+
+        // The component doesn't identify as a DWT anymore.
+        interface.write(0xFE0, 0x01);
+        assert_eq!(watchpoint_units(&mut interface, &components).unwrap(), 0);
+    }
+}
@@ -1,6 +1,7 @@
 use super::super::memory::romtable::CoresightComponent;
 use crate::architecture::arm::ArmProbeInterface;
 use crate::Error;
+use anyhow::anyhow;
 
 pub const _TPIU_PID: [u8; 8] = [0xA1, 0xB9, 0x0B, 0x0, 0x4, 0x0, 0x0, 0x0];
 
@@ -61,4 +62,28 @@ impl<'a> Tpiu<'a> {
             .write_reg(self.interface, REGISTER_OFFSET_TPIU_FFCR, value)?;
         Ok(())
     }
+
+    /// Configure the TPIU to output trace data over SWO.
+    ///
+    /// The TPIU is set up for a single bit port using the NRZ (UART) protocol, with the formatter
+    /// bypassed so that raw ITM and DWT packets are output. `baud` is the SWO baud rate and `clock`
+    /// the frequency of the TPIU trace clock, both in Hz.
+    ///
+    /// Returns the prescaler which was programmed, dividing `clock` by `prescaler + 1`.
+    pub fn configure_swo_output(&mut self, baud: u32, clock: u32) -> Result<u32, Error> {
+        if baud == 0 || baud > clock {
+            return Err(Error::Other(anyhow!(
+                "A baud rate of {baud} cannot be derived from a trace clock of {clock} Hz"
+            )));
+        }
+
+        let prescaler = (clock / baud) - 1;
+
+        self.set_port_size(1)?;
+        self.set_prescaler(prescaler)?;
+        self.set_pin_protocol(2)?;
+        self.set_formatter(0x100)?;
+
+        Ok(prescaler)
+    }
 }
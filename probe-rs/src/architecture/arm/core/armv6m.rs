@@ -1,6 +1,6 @@
 //! Register types and the core interface for armv6-M
 
-use super::{registers::cortex_m::*, CortexMState, Dfsr};
+use super::{read_debug_fault_status, registers::cortex_m::*, CortexMState};
 use crate::{
     architecture::arm::{
         memory::adi_v5_memory_interface::ArmProbe, sequences::ArmDebugSequence, ArmError,
@@ -427,11 +427,12 @@ impl<'probe> Armv6m<'probe> {
             // determine current state
             let dhcsr = Dhcsr(memory.read_word_32(Dhcsr::get_mmio_address())?);
 
+            // The bits in the DFSR are sticky, so they are cleared to ensure that none are set.
+            let dfsr = read_debug_fault_status(&mut *memory)?;
+
             let core_state = if dhcsr.s_sleep() {
                 CoreStatus::Sleeping
             } else if dhcsr.s_halt() {
-                let dfsr = Dfsr(memory.read_word_32(Dfsr::get_mmio_address())?);
-
                 let reason = dfsr.halt_reason();
 
                 tracing::debug!("Core was halted when connecting, reason: {:?}", reason);
@@ -441,12 +442,6 @@ impl<'probe> Armv6m<'probe> {
                 CoreStatus::Running
             };
 
-            // Clear DFSR register. The bits in the register are sticky,
-            // so we clear them here to ensure that that none are set.
-            let dfsr_clear = Dfsr::clear_all();
-
-            memory.write_word_32(Dfsr::get_mmio_address(), dfsr_clear.into())?;
-
             state.current_state = core_state;
             state.initialize();
         }
@@ -513,13 +508,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         // TODO: Handle lockup
 
         if dhcsr.s_halt() {
-            let dfsr = Dfsr(self.memory.read_word_32(Dfsr::get_mmio_address())?);
-
-            let mut reason = dfsr.halt_reason();
-
-            // Clear bits from Dfsr register
-            self.memory
-                .write_word_32(Dfsr::get_mmio_address(), Dfsr::clear_all().into())?;
+            let mut reason = read_debug_fault_status(&mut *self.memory)?.halt_reason();
 
             // If the core was halted before, we cannot read the halt reason from the chip,
             // because we clear it directly after reading.
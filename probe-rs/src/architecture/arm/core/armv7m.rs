@@ -2,10 +2,11 @@
 
 use super::{
     cortex_m::Mvfr0,
+    read_debug_fault_status,
     registers::cortex_m::{
         CORTEX_M_CORE_REGISTERS, CORTEX_M_WITH_FP_CORE_REGISTERS, FP, PC, RA, SP,
     },
-    CortexMState, Dfsr,
+    CortexMState,
 };
 use crate::{
     architecture::arm::{
@@ -608,11 +609,12 @@ impl<'probe> Armv7m<'probe> {
             // determine current state
             let dhcsr = Dhcsr(memory.read_word_32(Dhcsr::get_mmio_address())?);
 
+            // The bits in the DFSR are sticky, so they are cleared to ensure that none are set.
+            let dfsr = read_debug_fault_status(&mut *memory)?;
+
             let core_state = if dhcsr.s_sleep() {
                 CoreStatus::Sleeping
             } else if dhcsr.s_halt() {
-                let dfsr = Dfsr(memory.read_word_32(Dfsr::get_mmio_address())?);
-
                 let reason = dfsr.halt_reason();
 
                 tracing::debug!("Core was halted when connecting, reason: {:?}", reason);
@@ -622,12 +624,6 @@ impl<'probe> Armv7m<'probe> {
                 CoreStatus::Running
             };
 
-            // Clear DFSR register. The bits in the register are sticky,
-            // so we clear them here to ensure that that none are set.
-            let dfsr_clear = Dfsr::clear_all();
-
-            memory.write_word_32(Dfsr::get_mmio_address(), dfsr_clear.into())?;
-
             state.current_state = core_state;
             state.fp_present = Mvfr0(memory.read_word_32(Mvfr0::get_mmio_address())?).fp_present();
 
@@ -692,13 +688,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         }
 
         if dhcsr.s_halt() {
-            let dfsr = Dfsr(self.memory.read_word_32(Dfsr::get_mmio_address())?);
-
-            let mut reason = dfsr.halt_reason();
-
-            // Clear bits from Dfsr register
-            self.memory
-                .write_word_32(Dfsr::get_mmio_address(), Dfsr::clear_all().into())?;
+            let mut reason = read_debug_fault_status(&mut *self.memory)?.halt_reason();
 
             // If the core was halted before, we cannot read the halt reason from the chip,
             // because we clear it directly after reading.
@@ -2,9 +2,11 @@
 
 use crate::{
     core::{BreakpointCause, RegisterValue},
-    memory_mapped_bitfield_register, CoreStatus, HaltReason,
+    memory_mapped_bitfield_register, CoreStatus, HaltReason, MemoryMappedRegister,
 };
 
+use super::{memory::adi_v5_memory_interface::ArmProbe, ArmError};
+
 pub mod armv6m;
 pub mod armv7a;
 pub mod armv7m;
@@ -123,6 +125,17 @@ impl From<Dfsr> for u32 {
     }
 }
 
+/// Reads the DFSR of a Cortex-M core, and clears the debug events reported in it.
+///
+/// The bits of the DFSR are sticky, so they have to be cleared for the next halt to be
+/// attributed to the correct debug event.
+pub(crate) fn read_debug_fault_status(memory: &mut dyn ArmProbe) -> Result<Dfsr, ArmError> {
+    let dfsr = Dfsr::from(memory.read_word_32(Dfsr::get_mmio_address())?);
+    memory.write_word_32(Dfsr::get_mmio_address(), Dfsr::clear_all().into())?;
+
+    Ok(dfsr)
+}
+
 /// The state cache of a Cortex-M core.
 ///
 /// This state is used internally to not having to poll the core constantly.
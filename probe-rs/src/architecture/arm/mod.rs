@@ -24,7 +24,11 @@ pub use communication_interface::{
     ApInformation, ArmChipInfo, ArmCommunicationInterface, ArmProbeInterface, DapError,
     MemoryApInformation, Register,
 };
-pub use swo::{SwoAccess, SwoConfig, SwoMode, SwoReader};
+pub use swo::{
+    decode_dwt_packets, decode_exception_trace, decode_timestamped_dwt_packets, DwtPacket,
+    ExceptionAction, ExceptionTraceEvent, SwoAccess, SwoConfig, SwoMode, SwoReader,
+    TimestampedDwtPacket, TpiuDeformatter,
+};
 pub use traits::*;
 
 /// ARM-specific errors
@@ -88,6 +92,23 @@ pub enum ArmError {
     /// A region ouside of the AP address space was accessed.
     #[error("Out of bounds access")]
     OutOfBounds,
+    /// All comparators of the DWT are in use.
+    #[error("All DWT comparators are in use")]
+    NoFreeComparator,
+    /// Writing a register of a DWT comparator failed, the comparator was disabled.
+    #[error("Failed to write {register} of DWT comparator {unit}")]
+    DwtComparator {
+        /// The name of the register.
+        register: &'static str,
+        /// The index of the comparator.
+        unit: usize,
+        /// The error which occurred while writing the register.
+        #[source]
+        source: Box<ArmError>,
+    },
+    /// A watchpoint can't cover the given number of bytes.
+    #[error("A watchpoint cannot be {0} bytes long")]
+    InvalidWatchpointLength(u64),
     /// The requested memory transfer width is not supported on the current core.
     #[error("{0} bit is not a supported memory transfer width on the current core")]
     UnsupportedTransferWidth(usize),
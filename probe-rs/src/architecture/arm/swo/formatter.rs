@@ -0,0 +1,165 @@
+//! Removal of the TPIU formatter protocol from a trace stream.
+//!
+//! With continuous formatting enabled, the TPIU interleaves the data of all trace sources in
+//! frames of 16 bytes. See the CoreSight architecture specification D4 for the frame format.
+
+use std::collections::HashMap;
+
+/// The length of a formatter frame in bytes.
+const FRAME_LEN: usize = 16;
+
+/// A full synchronization packet, which is emitted between frames.
+const FULL_SYNC: [u8; 4] = [0xff, 0xff, 0xff, 0x7f];
+
+/// The source ID which marks data that has to be ignored.
+const NULL_ID: u8 = 0x00;
+
+/// Splits a TPIU formatted trace stream into the streams of the individual trace sources.
+///
+/// The ITM, whose stream also contains the DWT packets, usually uses source ID 1, but the ID
+/// is configured in the ITM and can differ on some targets.
+///
+/// The first frame is found by searching for a full synchronization packet, data before it is
+/// dropped. Incomplete frames are kept until more data is passed to [`TpiuDeformatter::push`].
+#[derive(Debug, Default)]
+pub struct TpiuDeformatter {
+    /// Data which doesn't form a complete frame yet.
+    pending: Vec<u8>,
+    /// Whether a synchronization packet was found, so `pending` starts at a frame boundary.
+    synchronized: bool,
+    /// The source ID of the data bytes.
+    id: Option<u8>,
+}
+
+impl TpiuDeformatter {
+    /// Creates a deformatter which waits for the first synchronization packet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the start of a frame has been found.
+    pub fn is_synchronized(&self) -> bool {
+        self.synchronized
+    }
+
+    /// Decodes the next part of the trace stream, and returns the data of each source ID.
+    pub fn push(&mut self, data: &[u8]) -> HashMap<u8, Vec<u8>> {
+        self.pending.extend_from_slice(data);
+
+        let mut streams = HashMap::new();
+        let mut offset = 0;
+
+        if !self.synchronized {
+            let Some(sync) = self
+                .pending
+                .windows(FULL_SYNC.len())
+                .position(|window| window == FULL_SYNC)
+            else {
+                // Keep the bytes which can be the start of a synchronization packet.
+                let keep = self.pending.len().min(FULL_SYNC.len() - 1);
+                self.pending.drain(..self.pending.len() - keep);
+                return streams;
+            };
+
+            self.synchronized = true;
+            offset = sync;
+        }
+
+        loop {
+            let rest = &self.pending[offset..];
+            if rest.starts_with(&FULL_SYNC) {
+                offset += FULL_SYNC.len();
+            } else if rest.len() >= FRAME_LEN {
+                let frame: [u8; FRAME_LEN] = rest[..FRAME_LEN].try_into().unwrap();
+                self.decode_frame(&frame, &mut streams);
+                offset += FRAME_LEN;
+            } else {
+                break;
+            }
+        }
+
+        self.pending.drain(..offset);
+        streams
+    }
+
+    fn decode_frame(&mut self, frame: &[u8; FRAME_LEN], streams: &mut HashMap<u8, Vec<u8>>) {
+        let aux = frame[FRAME_LEN - 1];
+
+        for (pair, bytes) in frame[..FRAME_LEN - 1].chunks(2).enumerate() {
+            let aux_bit = aux >> pair & 1;
+            let even = bytes[0];
+
+            // The ID of the following byte, if the source changes after it.
+            let mut delayed_id = None;
+
+            if even & 1 == 1 {
+                let id = even >> 1;
+                // The last even byte has no following data byte.
+                if aux_bit == 1 && bytes.len() == 2 {
+                    delayed_id = Some(id);
+                } else {
+                    self.id = Some(id);
+                }
+            } else {
+                self.emit(even | aux_bit, streams);
+            }
+
+            if let Some(&odd) = bytes.get(1) {
+                self.emit(odd, streams);
+            }
+
+            if delayed_id.is_some() {
+                self.id = delayed_id;
+            }
+        }
+    }
+
+    fn emit(&self, byte: u8, streams: &mut HashMap<u8, Vec<u8>>) {
+        match self.id {
+            Some(id) if id != NULL_ID => streams.entry(id).or_default().push(byte),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TpiuDeformatter;
+
+    #[test]
+    fn split_sources() {
+        let frame = [
+            // ID 1, taking effect immediately
+            0x03, 0x11, //
+            // Data with the LSB in the auxiliary byte
+            0x22, 0x33, //
+            // ID 2, taking effect after the next byte
+            0x05, 0x44, //
+            // Data
+            0x54, 0x66, 0x76, 0x88, 0x98, 0xaa, 0xba, 0xcc, 0xdc, //
+            // Auxiliary byte, bits 1 and 2 set
+            0x06,
+        ];
+
+        let mut deformatter = TpiuDeformatter::new();
+
+        // Noise before the synchronization packet is dropped.
+        let streams = deformatter.push(&[0x12, 0xff, 0xff]);
+        assert!(streams.is_empty());
+        assert!(!deformatter.is_synchronized());
+
+        let streams = deformatter.push(&[0xff, 0x7f]);
+        assert!(streams.is_empty());
+        assert!(deformatter.is_synchronized());
+
+        // The frame is split into two calls.
+        assert!(deformatter.push(&frame[..7]).is_empty());
+        let streams = deformatter.push(&frame[7..]);
+
+        assert_eq!(streams[&1], [0x11, 0x23, 0x33, 0x44]);
+        assert_eq!(
+            streams[&2],
+            [0x54, 0x66, 0x76, 0x88, 0x98, 0xaa, 0xba, 0xcc, 0xdc]
+        );
+    }
+}
@@ -1,5 +1,14 @@
 //! SWO tracing related functions.
 
+mod formatter;
+mod packet;
+
+pub use formatter::TpiuDeformatter;
+pub use packet::{
+    decode_dwt_packets, decode_exception_trace, decode_timestamped_dwt_packets, DwtPacket,
+    ExceptionAction, ExceptionTraceEvent, TimestampedDwtPacket,
+};
+
 use crate::architecture::arm::communication_interface::ArmProbeInterface;
 
 use super::ArmError;
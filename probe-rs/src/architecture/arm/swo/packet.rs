@@ -0,0 +1,489 @@
+//! Decoding of the ITM packet stream received over SWO.
+//!
+//! See ARMv7-M architecture reference manual D4 for the packet formats.
+
+/// A packet of the ITM stream generated by the DWT data trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DwtPacket {
+    /// A synchronization packet.
+    Sync,
+    /// An overflow packet, some packets were lost.
+    Overflow,
+    /// The PC of an access which matched comparator `comparator`.
+    DataTracePc {
+        /// The index of the matching comparator.
+        comparator: u8,
+        /// The program counter of the accessing instruction.
+        pc: u32,
+    },
+    /// The address offset of an access which matched comparator `comparator`.
+    DataTraceAddress {
+        /// The index of the matching comparator.
+        comparator: u8,
+        /// The low 16 bits of the accessed address.
+        offset: u16,
+    },
+    /// The data value of an access which matched comparator `comparator`.
+    DataTraceValue {
+        /// The index of the matching comparator.
+        comparator: u8,
+        /// Whether the access was a write.
+        write: bool,
+        /// The value which was read or written.
+        value: u32,
+    },
+}
+
+/// A [`DwtPacket`] with the time it was generated, reconstructed from the local timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampedDwtPacket {
+    /// The decoded packet.
+    pub packet: DwtPacket,
+    /// The time of the packet in timestamp clock cycles, relative to the start of the stream.
+    ///
+    /// Packets after the last timestamp of the stream have no time.
+    pub timestamp: Option<u64>,
+    /// Whether the timestamp is not exact, because the ITM reported it as delayed, or the
+    /// timestamp counter overflowed.
+    pub delayed: bool,
+}
+
+/// What happened to an exception, as reported by an exception trace packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionAction {
+    /// The exception was entered.
+    Entered,
+    /// The exception was exited.
+    Exited,
+    /// Execution returned to the exception.
+    Returned,
+}
+
+/// An exception trace packet generated by the DWT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionTraceEvent {
+    /// The exception number, external interrupts start at 16.
+    pub exception_number: u16,
+    /// What happened to the exception.
+    pub action: ExceptionAction,
+}
+
+impl ExceptionTraceEvent {
+    /// Returns the name of the exception.
+    ///
+    /// Architectural exceptions are named by probe-rs. For external interrupts, `external` is
+    /// called with the interrupt number, which is the exception number minus 16, for example to
+    /// look up the name in the SVD of the target. If it returns `None`, `IRQ <n>` is used.
+    pub fn exception_name(&self, external: impl FnOnce(u16) -> Option<String>) -> String {
+        let name = match self.exception_number {
+            0 => "Thread",
+            1 => "Reset",
+            2 => "NMI",
+            3 => "HardFault",
+            4 => "MemManage",
+            5 => "BusFault",
+            6 => "UsageFault",
+            7 => "SecureFault",
+            11 => "SVCall",
+            12 => "DebugMonitor",
+            14 => "PendSV",
+            15 => "SysTick",
+            number @ 16.. => {
+                let irq = number - 16;
+                return external(irq).unwrap_or_else(|| format!("IRQ {irq}"));
+            }
+            number => return format!("Reserved {number}"),
+        };
+
+        name.to_string()
+    }
+}
+
+/// A packet of the ITM stream, as far as it is needed for decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RawPacket {
+    /// A synchronization packet.
+    Sync,
+    /// An overflow packet.
+    Overflow,
+    /// A software (instrumentation) or hardware source packet.
+    Source {
+        /// Whether the packet was generated by the DWT.
+        hardware: bool,
+        /// The stimulus port or the hardware discriminator.
+        address: u8,
+        /// The payload, little endian.
+        payload: u32,
+    },
+    /// A local timestamp packet.
+    LocalTimestamp {
+        /// The time since the previous timestamp.
+        delta: u32,
+        /// The TC field, which reports whether the timestamp or the packets are delayed.
+        control: u8,
+    },
+    /// A global timestamp or extension packet, which is not decoded.
+    Other,
+}
+
+/// Splits `data` into ITM packets.
+///
+/// The stream has to start at a packet boundary. A truncated packet at the end of `data` is
+/// dropped.
+pub(crate) fn raw_packets(data: &[u8]) -> Vec<RawPacket> {
+    let mut packets = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let header = data[i];
+        i += 1;
+
+        match header {
+            // At least 47 zero bits followed by a one.
+            0x00 => {
+                while i < data.len() && data[i] == 0x00 {
+                    i += 1;
+                }
+                if i == data.len() {
+                    break;
+                }
+                if data[i] == 0x80 {
+                    packets.push(RawPacket::Sync);
+                }
+                i += 1;
+            }
+            0x70 => packets.push(RawPacket::Overflow),
+            _ if header & 0b11 != 0 => {
+                let size = match header & 0b11 {
+                    0b01 => 1,
+                    0b10 => 2,
+                    _ => 4,
+                };
+                let Some(bytes) = data.get(i..i + size) else {
+                    break;
+                };
+                i += size;
+
+                let payload = bytes
+                    .iter()
+                    .rev()
+                    .fold(0, |payload, &byte| payload << 8 | byte as u32);
+
+                packets.push(RawPacket::Source {
+                    hardware: header & 0b100 != 0,
+                    address: header >> 3,
+                    payload,
+                });
+            }
+            // A local timestamp with up to four continuation bytes of 7 bits each.
+            _ if header & 0xcf == 0xc0 => {
+                let mut delta = 0;
+                let mut shift = 0;
+                let mut byte = header;
+                while byte & 0x80 != 0 {
+                    let Some(&next) = data.get(i) else {
+                        return packets;
+                    };
+                    if shift < 28 {
+                        delta |= ((next & 0x7f) as u32) << shift;
+                    }
+                    shift += 7;
+                    byte = next;
+                    i += 1;
+                }
+                packets.push(RawPacket::LocalTimestamp {
+                    delta,
+                    control: (header >> 4) & 0b11,
+                });
+            }
+            // A compressed local timestamp, whose value of 1 to 6 is part of the header.
+            _ if header & 0x8f == 0 => packets.push(RawPacket::LocalTimestamp {
+                delta: (header >> 4) as u32,
+                control: 0,
+            }),
+            _ => {
+                // Global timestamp and extension packets have continuation bytes while bit 7 is set.
+                let mut byte = header;
+                while byte & 0x80 != 0 {
+                    let Some(&next) = data.get(i) else {
+                        return packets;
+                    };
+                    byte = next;
+                    i += 1;
+                }
+                packets.push(RawPacket::Other);
+            }
+        }
+    }
+
+    packets
+}
+
+/// Decodes the DWT data trace packets contained in the ITM stream `data`.
+///
+/// Synchronization and overflow packets are kept to allow detecting gaps in the trace, all other
+/// packets are skipped.
+pub fn decode_dwt_packets(data: &[u8]) -> Vec<DwtPacket> {
+    raw_packets(data)
+        .into_iter()
+        .filter_map(dwt_packet)
+        .collect()
+}
+
+/// Decodes the DWT data trace packets contained in the ITM stream `data`, like
+/// [`decode_dwt_packets`], and adds the time at which they were generated.
+///
+/// The ITM emits a local timestamp after the packets it applies to, with the time since the
+/// previous timestamp. The deltas are accumulated, so the time is relative to the start of
+/// `data`. A delta with all 28 bits set means the timestamp counter overflowed, the packets were
+/// generated at least that much later.
+pub fn decode_timestamped_dwt_packets(data: &[u8]) -> Vec<TimestampedDwtPacket> {
+    let mut packets = Vec::new();
+    // The index of the first packet which has no timestamp yet.
+    let mut pending = 0;
+    let mut time = 0u64;
+
+    for packet in raw_packets(data) {
+        if let RawPacket::LocalTimestamp { delta, control } = packet {
+            time += delta as u64;
+            let delayed = control != 0 || delta == 0x0fff_ffff;
+
+            for packet in &mut packets[pending..] {
+                *packet = TimestampedDwtPacket {
+                    timestamp: Some(time),
+                    delayed,
+                    ..*packet
+                };
+            }
+            pending = packets.len();
+        } else if let Some(packet) = dwt_packet(packet) {
+            packets.push(TimestampedDwtPacket {
+                packet,
+                timestamp: None,
+                delayed: false,
+            });
+        }
+    }
+
+    packets
+}
+
+fn dwt_packet(packet: RawPacket) -> Option<DwtPacket> {
+    match packet {
+        RawPacket::Sync => Some(DwtPacket::Sync),
+        RawPacket::Overflow => Some(DwtPacket::Overflow),
+        RawPacket::Source {
+            hardware: true,
+            address,
+            payload,
+        } => {
+            let comparator = (address >> 1) & 0b11;
+            match address >> 3 {
+                0b01 if address & 1 == 0 => Some(DwtPacket::DataTracePc {
+                    comparator,
+                    pc: payload,
+                }),
+                0b01 => Some(DwtPacket::DataTraceAddress {
+                    comparator,
+                    offset: payload as u16,
+                }),
+                0b10 => Some(DwtPacket::DataTraceValue {
+                    comparator,
+                    write: address & 1 != 0,
+                    value: payload,
+                }),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Decodes the exception trace packets contained in the ITM stream `data`.
+///
+/// All other packets are skipped.
+pub fn decode_exception_trace(data: &[u8]) -> Vec<ExceptionTraceEvent> {
+    raw_packets(data)
+        .into_iter()
+        .filter_map(|packet| match packet {
+            RawPacket::Source {
+                hardware: true,
+                address: 1,
+                payload,
+            } => {
+                let action = match (payload >> 12) & 0b11 {
+                    0b01 => ExceptionAction::Entered,
+                    0b10 => ExceptionAction::Exited,
+                    0b11 => ExceptionAction::Returned,
+                    _ => return None,
+                };
+
+                Some(ExceptionTraceEvent {
+                    exception_number: (payload & 0x1ff) as u16,
+                    action,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_dwt_packets, decode_exception_trace, decode_timestamped_dwt_packets, DwtPacket,
+        ExceptionAction, ExceptionTraceEvent, TimestampedDwtPacket,
+    };
+
+    #[test]
+    fn decode_data_trace() {
+        let data = [
+            // Sync
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x80, //
+            // PC of comparator 1
+            0x57, 0x34, 0x12, 0x00, 0x08, //
+            // Write of a halfword value by comparator 1
+            0x9e, 0xcd, 0xab, //
+            // Local timestamp with a continuation byte
+            0xc0, 0x05, //
+            // Instrumentation packet on port 0
+            0x01, 0x41, //
+            // Address offset of comparator 2
+            0x6e, 0x04, 0x00, //
+            // Overflow
+            0x70, //
+            // Truncated read value of comparator 0
+            0x87, 0x01,
+        ];
+
+        assert_eq!(
+            decode_dwt_packets(&data),
+            [
+                DwtPacket::Sync,
+                DwtPacket::DataTracePc {
+                    comparator: 1,
+                    pc: 0x0800_1234
+                },
+                DwtPacket::DataTraceValue {
+                    comparator: 1,
+                    write: true,
+                    value: 0xabcd
+                },
+                DwtPacket::DataTraceAddress {
+                    comparator: 2,
+                    offset: 0x0004
+                },
+                DwtPacket::Overflow,
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_timestamps() {
+        let data = [
+            // PC of comparator 0
+            0x47, 0x00, 0x01, 0x00, 0x08, //
+            // Read of a byte value by comparator 0
+            0x85, 0x42, //
+            // Compressed local timestamp of 3
+            0x30, //
+            // Address offset of comparator 1
+            0x5d, 0x10, //
+            // Delayed local timestamp of 0x81 = 129
+            0xd0, 0x81, 0x01, //
+            // Write of a byte value by comparator 1
+            0x9d, 0x7f, //
+            // Overflowed local timestamp
+            0xc0, 0xff, 0xff, 0xff, 0x7f, //
+            // Overflow, not followed by a timestamp
+            0x70,
+        ];
+
+        let packet = |packet, timestamp, delayed| TimestampedDwtPacket {
+            packet,
+            timestamp,
+            delayed,
+        };
+
+        assert_eq!(
+            decode_timestamped_dwt_packets(&data),
+            [
+                packet(
+                    DwtPacket::DataTracePc {
+                        comparator: 0,
+                        pc: 0x0800_0100
+                    },
+                    Some(3),
+                    false
+                ),
+                packet(
+                    DwtPacket::DataTraceValue {
+                        comparator: 0,
+                        write: false,
+                        value: 0x42
+                    },
+                    Some(3),
+                    false
+                ),
+                packet(
+                    DwtPacket::DataTraceAddress {
+                        comparator: 1,
+                        offset: 0x10
+                    },
+                    Some(132),
+                    true
+                ),
+                packet(
+                    DwtPacket::DataTraceValue {
+                        comparator: 1,
+                        write: true,
+                        value: 0x7f
+                    },
+                    Some(132 + 0x0fff_ffff),
+                    true
+                ),
+                packet(DwtPacket::Overflow, None, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_exceptions() {
+        let data = [
+            // SysTick entered
+            0x0e, 0x0f, 0x10, //
+            // Data value of comparator 0
+            0x85, 0x01, //
+            // IRQ 1 exited
+            0x0e, 0x11, 0x20, //
+            // Thread mode returned
+            0x0e, 0x00, 0x30,
+        ];
+
+        let events = decode_exception_trace(&data);
+        assert_eq!(
+            events,
+            [
+                ExceptionTraceEvent {
+                    exception_number: 15,
+                    action: ExceptionAction::Entered
+                },
+                ExceptionTraceEvent {
+                    exception_number: 17,
+                    action: ExceptionAction::Exited
+                },
+                ExceptionTraceEvent {
+                    exception_number: 0,
+                    action: ExceptionAction::Returned
+                },
+            ]
+        );
+
+        assert_eq!(events[0].exception_name(|_| None), "SysTick");
+        assert_eq!(events[1].exception_name(|_| None), "IRQ 1");
+        assert_eq!(
+            events[1].exception_name(|irq| (irq == 1).then(|| "UART0".to_string())),
+            "UART0"
+        );
+    }
+}
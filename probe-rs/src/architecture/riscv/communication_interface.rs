@@ -63,6 +63,14 @@ pub enum RiscvError {
     /// The given trigger type is not available for the address breakpoint.
     #[error("Unexpected trigger type {0} for address breakpoint.")]
     UnexpectedTriggerType(u32),
+    /// A trigger cannot watch the region, which is not a naturally aligned power of two bytes.
+    #[error("Cannot watch {length} bytes at {address:#010x}, the region must be a naturally aligned power of two bytes.")]
+    InvalidWatchpointRegion {
+        /// The start address of the region.
+        address: u32,
+        /// The length of the region in bytes.
+        length: u32,
+    },
     /// The connected target is not a RISCV device.
     #[error("Connected target is not a RISCV device.")]
     NoRiscvTarget,
@@ -3,6 +3,7 @@
 #![allow(clippy::inconsistent_digit_grouping)]
 
 use self::registers::*;
+use crate::architecture::arm::component::WatchKind;
 use crate::{
     core::{
         Architecture, BreakpointCause, CoreInformation, CoreRegisters, RegisterId, RegisterValue,
@@ -511,8 +512,41 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         Ok(tselect_index)
     }
 
+    /// See docs on the [`CoreInterface::hw_watchpoints`] trait
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<u64>>, Error> {
+        let tselect = 0x7a0;
+        let tdata1 = 0x7a1;
+        let tdata2 = 0x7a2;
+
+        let mut watchpoints = vec![];
+        let num_hw_breakpoints = self.available_breakpoint_units()? as usize;
+        for unit_index in 0..num_hw_breakpoints {
+            self.write_csr(tselect, unit_index as u32)?;
+
+            let tdata_value = Mcontrol(self.read_csr(tdata1)?);
+
+            let trigger_any_mode_active = tdata_value.m() || tdata_value.s() || tdata_value.u();
+
+            // Only data access triggers are watchpoints, execution triggers are breakpoints.
+            if tdata_value.type_() == 0b10
+                && tdata_value.action() == 1
+                && matches!(tdata_value.match_(), 0 | 1)
+                && trigger_any_mode_active
+                && !tdata_value.execute()
+                && (tdata_value.store() || tdata_value.load())
+            {
+                let watchpoint = trigger_address(&tdata_value, self.read_csr(tdata2)?);
+                watchpoints.push(Some(watchpoint as u64));
+            } else {
+                watchpoints.push(None);
+            }
+        }
+
+        Ok(watchpoints)
+    }
+
     /// See docs on the [`CoreInterface::hw_breakpoints`] trait
-    /// NOTE: For riscv, this assumes that only execution breakpoints are used.
+    /// NOTE: For riscv, this also returns the triggers used for watchpoints.
     fn hw_breakpoints(&mut self) -> Result<Vec<Option<u64>>, Error> {
         let tselect = 0x7a0;
         let tdata1 = 0x7a1;
@@ -538,11 +572,11 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             // Only return if the trigger if it is for an execution debug action in all modes.
             if tdata_value.type_() == 0b10
                 && tdata_value.action() == 1
-                && tdata_value.match_() == 0
+                && matches!(tdata_value.match_(), 0 | 1)
                 && trigger_any_mode_active
                 && trigger_any_action_enabled
             {
-                let breakpoint = self.read_csr(tdata2)?;
+                let breakpoint = trigger_address(&tdata_value, self.read_csr(tdata2)?);
                 breakpoints.push(Some(breakpoint as u64));
             } else {
                 breakpoints.push(None);
@@ -641,6 +675,86 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         Ok(())
     }
 
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        addr: u64,
+        length: u32,
+        kind: WatchKind,
+    ) -> Result<(), crate::Error> {
+        let addr = valid_32bit_address(addr)?;
+
+        // Triggers match a single address, or a naturally aligned power-of-two region.
+        if !length.is_power_of_two() || addr % length != 0 {
+            return Err(RiscvError::InvalidWatchpointRegion {
+                address: addr,
+                length,
+            }
+            .into());
+        }
+
+        let tselect = 0x7a0;
+        let tdata1 = 0x7a1;
+        let tdata2 = 0x7a2;
+
+        self.write_csr(tselect, unit_index as u32)?;
+
+        // verify the trigger has the correct type
+        let tdata_value = Mcontrol(self.read_csr(tdata1)?);
+
+        let trigger_type = tdata_value.type_();
+        if trigger_type != 0b10 {
+            return Err(RiscvError::UnexpectedTriggerType(trigger_type).into());
+        }
+
+        let mut watchpoint = Mcontrol(0);
+
+        // Enter debug mode
+        watchpoint.set_action(1);
+
+        // Regions of up to 2^maskmax bytes are matched with the NAPOT encoding in tdata2,
+        // otherwise only accesses to the start address trigger.
+        let napot = length > 1 && length.trailing_zeros() <= tdata_value.maskmax();
+        let tdata2_value = if napot {
+            watchpoint.set_match(1);
+            addr | ((length >> 1) - 1)
+        } else {
+            if length > 1 {
+                tracing::warn!(
+                    "Trigger {} cannot match {} bytes, only accesses to {:#010x} are watched",
+                    unit_index,
+                    length,
+                    addr
+                );
+            }
+            watchpoint.set_match(0);
+            addr
+        };
+
+        watchpoint.set_m(true);
+
+        watchpoint.set_u(true);
+
+        // Trigger on the requested data accesses
+        let (load, store) = match kind {
+            WatchKind::Read => (true, false),
+            WatchKind::Write => (false, true),
+            WatchKind::ReadWrite => (true, true),
+        };
+        watchpoint.set_load(load);
+        watchpoint.set_store(store);
+
+        watchpoint.set_dmode(true);
+
+        // Match address
+        watchpoint.set_select(false);
+
+        self.write_csr(tdata1, watchpoint.0)?;
+        self.write_csr(tdata2, tdata2_value)?;
+
+        Ok(())
+    }
+
     fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), crate::Error> {
         let tselect = 0x7a0;
         let tdata1 = 0x7a1;
@@ -1001,6 +1115,18 @@ bitfield! {
     load, set_load: 0;
 }
 
+/// Returns the start address matched by the address trigger `mcontrol`, whose tdata2 register
+/// is `tdata2`.
+///
+/// With the NAPOT match, the trailing ones of tdata2 encode the size of the region.
+fn trigger_address(mcontrol: &Mcontrol, tdata2: u32) -> u32 {
+    if mcontrol.match_() == 1 {
+        tdata2 & tdata2.wrapping_add(1)
+    } else {
+        tdata2
+    }
+}
+
 bitfield! {
     /// Isa and Extensions (see RISC-V Privileged Spec, 3.1.1)
     pub struct Misa(u32);
@@ -7,7 +7,7 @@ use probe_rs::{
         arm::{
             ap::{GenericAp, MemoryAp},
             armv6m::Demcr,
-            component::Scs,
+            component::{Dwt, Scs},
             dp::{DPIDR, TARGETID},
             memory::{Component, CoresightComponent, PeripheralType},
             sequences::DefaultArmSequence,
@@ -330,6 +330,12 @@ fn coresight_component_tree(
                 let cpu_tree = cpu_info_tree(scs)?;
 
                 tree.push(cpu_tree);
+            } else if peripheral_id.is_of_type(PeripheralType::Dwt) {
+                let cc = &CoresightComponent::new(component, access_port);
+                let dwt = &mut Dwt::new(interface, cc);
+                let dwt_tree = dwt_info_tree(dwt)?;
+
+                tree.push(dwt_tree);
             }
 
             tree
@@ -363,6 +369,20 @@ fn cpu_info_tree(scs: &mut Scs) -> Result<Tree<String>> {
     Ok(tree)
 }
 
+fn dwt_info_tree(dwt: &mut Dwt) -> Result<Tree<String>> {
+    let mut tree = Tree::new("Capabilities".into());
+
+    let capabilities = dwt.capabilities()?;
+
+    tree.push(format!("Comparators: {}", capabilities.numcomp));
+    tree.push(format!("Trace sampling: {}", capabilities.trace_sampling));
+    tree.push(format!("Compare match: {}", capabilities.compare_match));
+    tree.push(format!("Cycle counter: {}", capabilities.cyccnt));
+    tree.push(format!("Profiling counters: {}", capabilities.perf_counter));
+
+    Ok(tree)
+}
+
 fn show_riscv_info(interface: &mut RiscvCommunicationInterface) -> Result<()> {
     let idcode = interface.read_idcode()?;
 
@@ -7,7 +7,7 @@ use anyhow::Context;
 use itm::TracePacket;
 use probe_rs::{
     architecture::arm::{
-        component::{find_component, Dwt, TraceSink},
+        component::{find_component, Dwt, SyncTap, TraceSink},
         memory::PeripheralType,
         DpAddress, SwoConfig,
     },
@@ -138,7 +138,7 @@ impl ProfileCmd {
                 let component = find_component(&components, PeripheralType::Dwt)?;
                 let interface = session.get_arm_interface()?;
                 let mut dwt = Dwt::new(interface, component);
-                dwt.enable_pc_sampling()?;
+                dwt.enable_pc_sampling(SyncTap::Cyccnt24)?;
 
                 let decoder = itm::Decoder::new(
                     session.swo_reader()?,
@@ -2,11 +2,13 @@ use std::fs::File;
 use std::io::Write;
 use std::ops::Range;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
+use probe_rs::architecture::arm::component::WatchKind;
 use probe_rs::debug::{DebugInfo, DebugRegisters};
 use probe_rs::flashing::{FileDownloadError, Format};
 use probe_rs::{
@@ -52,6 +54,55 @@ pub struct Cmd {
 
     #[clap(long)]
     pub(crate) log_format: Option<String>,
+
+    /// Set a data watchpoint on core 0, which halts the core when it trips. Can be given multiple times.
+    ///
+    /// The length defaults to 4 bytes and the kind, which is one of read, write or access, to write.
+    #[clap(long = "watch", value_name = "ADDRESS[:LENGTH[:KIND]]")]
+    pub(crate) watch: Vec<WatchSpec>,
+}
+
+/// A data watchpoint given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WatchSpec {
+    address: u32,
+    length: u32,
+    kind: WatchKind,
+}
+
+impl FromStr for WatchSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+
+        let address = parts.next().unwrap_or_default();
+        let address = parse_int::parse(address)
+            .map_err(|e| format!("Invalid watchpoint address '{address}': {e}"))?;
+
+        let length = match parts.next() {
+            Some(length) => parse_int::parse(length)
+                .map_err(|e| format!("Invalid watchpoint length '{length}': {e}"))?,
+            None => 4,
+        };
+
+        let kind = match parts.next() {
+            Some(kind) => kind.parse()?,
+            None => WatchKind::Write,
+        };
+
+        if parts.next().is_some() {
+            return Err(format!(
+                "Invalid watchpoint '{s}', expected ADDRESS[:LENGTH[:KIND]]"
+            ));
+        }
+
+        Ok(WatchSpec {
+            address,
+            length,
+            kind,
+        })
+    }
 }
 
 impl Cmd {
@@ -95,14 +146,27 @@ impl Cmd {
 
         let memory_map = session.target().memory_map.clone();
         let rtt_scan_regions = session.target().rtt_scan_regions.clone();
-        let mut core = session.core(0)?;
 
         if run_download {
+            let mut core = session.core(0)?;
             core.reset_and_halt(Duration::from_millis(100))?;
             match core.enable_vector_catch(VectorCatchCondition::All) {
                 Ok(_) | Err(Error::NotImplemented(_)) => {} // Don't output an error if vector_catch hasn't been implemented
                 Err(e) => tracing::error!("Failed to enable_vector_catch: {:?}", e),
             }
+        }
+
+        for watch in &self.watch {
+            session
+                .add_data_watchpoint(0, watch.address, watch.length, watch.kind)
+                .with_context(|| {
+                    format!("Failed to set a watchpoint on {:#010x}", watch.address)
+                })?;
+        }
+
+        let mut core = session.core(0)?;
+
+        if run_download {
             core.run()?;
         }
 
@@ -207,11 +271,22 @@ fn run_loop(
             )) => Err(anyhow!(
                 "Semihosting indicates exit with failure code: {code:#08x} ({code})"
             )),
+            HaltReason::Watchpoint => {
+                match core.matched_data_watchpoint() {
+                    Ok(Some(watchpoint)) => println!(
+                        "Watchpoint hit: {} access to {:#010x} ({} bytes)",
+                        watchpoint.kind, watchpoint.address, watchpoint.length
+                    ),
+                    _ => println!("Watchpoint hit"),
+                }
+                Ok(())
+            }
             _ => Err(anyhow!("CPU halted unexpectedly.")),
         },
     };
 
-    if always_print_stacktrace || result.is_err() {
+    let watchpoint_hit = halt_reason == Some(HaltReason::Watchpoint);
+    if always_print_stacktrace || watchpoint_hit || result.is_err() {
         print_stacktrace(core, path)?;
     }
 
@@ -328,3 +403,31 @@ fn attach_to_rtt(
     log::error!("Failed to attach to RTT continuing...");
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::WatchSpec;
+    use probe_rs::architecture::arm::component::WatchKind;
+
+    #[test]
+    fn parse_watch_spec() {
+        assert_eq!(
+            "0x2000_0000".parse(),
+            Ok(WatchSpec {
+                address: 0x2000_0000,
+                length: 4,
+                kind: WatchKind::Write,
+            })
+        );
+        assert_eq!(
+            "0x20000010:2:rw".parse(),
+            Ok(WatchSpec {
+                address: 0x2000_0010,
+                length: 2,
+                kind: WatchKind::ReadWrite,
+            })
+        );
+        assert!("0x20000010:2:rw:1".parse::<WatchSpec>().is_err());
+        assert!("ram".parse::<WatchSpec>().is_err());
+    }
+}
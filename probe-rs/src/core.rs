@@ -1,6 +1,7 @@
 use crate::{
     architecture::{
         arm::{
+            component::{WatchKind, WatchpointConfig},
             core::registers::{
                 aarch32::{
                     AARCH32_CORE_REGSISTERS, AARCH32_WITH_FP_16_CORE_REGSISTERS,
@@ -106,6 +107,13 @@ pub trait CoreInterface: MemoryInterface {
     /// We intentionally return all breakpoints, irrespective of whether they are enabled or not.
     fn hw_breakpoints(&mut self) -> Result<Vec<Option<u64>>, error::Error>;
 
+    /// Read the hardware watchpoints which occupy breakpoint units, in the same layout as
+    /// [`CoreInterface::hw_breakpoints`]. A value of None indicates that the unit does not
+    /// hold a watchpoint.
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<u64>>, error::Error> {
+        Ok(vec![None; self.available_breakpoint_units()? as usize])
+    }
+
     /// Enables breakpoints on this core. If a breakpoint is set, it will halt as soon as it is hit.
     fn enable_breakpoints(&mut self, state: bool) -> Result<(), error::Error>;
 
@@ -115,6 +123,23 @@ pub trait CoreInterface: MemoryInterface {
     /// Clears the breakpoint configured in unit `unit_index`.
     fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), error::Error>;
 
+    /// Sets a data watchpoint on `length` bytes starting at `addr`. It does so by using unit
+    /// `unit_index`.
+    ///
+    /// The watchpoint is cleared with [`CoreInterface::clear_hw_breakpoint`], and reported by
+    /// [`CoreInterface::hw_watchpoints`]. Cores which
+    /// implement watchpoints outside of their breakpoint units, like ARM cores with
+    /// a DWT, do not support this.
+    fn set_hw_watchpoint(
+        &mut self,
+        _unit_index: usize,
+        _addr: u64,
+        _length: u32,
+        _kind: WatchKind,
+    ) -> Result<(), error::Error> {
+        Err(error::Error::NotImplemented("hardware watchpoints"))
+    }
+
     /// Returns a list of all the registers of this core.
     fn registers(&self) -> &'static registers::CoreRegisters;
 
@@ -785,6 +810,88 @@ impl<'probe> Core<'probe> {
         self.inner.available_breakpoint_units()
     }
 
+    /// Returns the number of breakpoint units of the core which are not in use.
+    pub fn free_breakpoint_units(&mut self) -> Result<usize, error::Error> {
+        let breakpoints = self.inner.hw_breakpoints()?;
+        Ok(breakpoints.iter().filter(|bp| bp.is_none()).count())
+    }
+
+    /// Returns the address of the breakpoint set in each breakpoint unit of the core.
+    ///
+    /// Units which are not in use are `None`.
+    pub fn list_hw_breakpoints(&mut self) -> Result<Vec<Option<u64>>, error::Error> {
+        self.inner.hw_breakpoints()
+    }
+
+    /// Returns whether a hardware breakpoint can be set at `address`.
+    ///
+    /// This is the case if a breakpoint unit is free, or if a breakpoint is already set at
+    /// `address`, without changing the configuration of the core.
+    pub fn can_set_hw_breakpoint(&mut self, address: u64) -> Result<bool, error::Error> {
+        let breakpoints = self.inner.hw_breakpoints()?;
+        Ok(breakpoints
+            .iter()
+            .any(|&bp| bp.is_none() || bp == Some(address)))
+    }
+
+    /// Returns the number of units of the core which can still be used for data watchpoints.
+    ///
+    /// On RISC-V, watchpoints share the trigger units with breakpoints. On Cortex-M cores,
+    /// the comparators of the DWT are used.
+    pub fn free_watchpoint_units(&mut self) -> Result<usize, error::Error> {
+        match self.core_type() {
+            CoreType::Riscv => self.free_breakpoint_units(),
+            core_type if core_type.is_cortex_m() => {
+                crate::architecture::arm::component::cortex_m_free_comparators(self)
+            }
+            _ => Err(error::Error::NotImplemented("hardware watchpoints")),
+        }
+    }
+
+    /// Returns the data watchpoints which are configured in the DWT of the core.
+    ///
+    /// This is only supported on Cortex-M cores.
+    pub fn list_data_watchpoints(&mut self) -> Result<Vec<WatchpointConfig>, error::Error> {
+        if !self.core_type().is_cortex_m() {
+            return Err(error::Error::NotImplemented("listing data watchpoints"));
+        }
+
+        crate::architecture::arm::component::cortex_m_watchpoints(self)
+    }
+
+    /// Returns the data watchpoint which caused the core to halt, if any.
+    ///
+    /// This clears the match flags of the DWT comparators, so it only reports the watchpoint
+    /// once per hit. This is only supported on Cortex-M cores.
+    pub fn matched_data_watchpoint(&mut self) -> Result<Option<WatchpointConfig>, error::Error> {
+        if !self.core_type().is_cortex_m() {
+            return Err(error::Error::NotImplemented(
+                "reporting matched data watchpoints",
+            ));
+        }
+
+        crate::architecture::arm::component::cortex_m_matched_watchpoint(self)
+    }
+
+    /// Returns the data watchpoint which was hit, if the core is halted because of one.
+    ///
+    /// The halt reason is taken from [`Core::status`], which reports
+    /// [`HaltReason::Watchpoint`] if `DWTTRAP` is set in the DFSR. The watched address and kind
+    /// of access are then found by [`Core::matched_data_watchpoint`]. `None` is returned if the
+    /// core is running or halted for another reason. This is only supported on Cortex-M cores.
+    pub fn on_watchpoint_hit(&mut self) -> Result<Option<WatchpointConfig>, error::Error> {
+        if !self.core_type().is_cortex_m() {
+            return Err(error::Error::NotImplemented(
+                "reporting matched data watchpoints",
+            ));
+        }
+
+        match self.status()? {
+            CoreStatus::Halted(HaltReason::Watchpoint) => self.matched_data_watchpoint(),
+            _ => Ok(None),
+        }
+    }
+
     /// Enables breakpoints on this core. If a breakpoint is set, it will halt as soon as it is hit.
     fn enable_breakpoints(&mut self, state: bool) -> Result<(), error::Error> {
         self.inner.enable_breakpoints(state)
@@ -876,11 +983,13 @@ impl<'probe> Core<'probe> {
     /// This function will try to clear a hardware breakpoint at `address` if there exists a breakpoint at that address.
     #[tracing::instrument(skip(self))]
     pub fn clear_hw_breakpoint(&mut self, address: u64) -> Result<(), error::Error> {
+        let watchpoints = self.inner.hw_watchpoints()?;
         let bp_position = self
             .inner
             .hw_breakpoints()?
             .iter()
-            .position(|bp| bp.is_some() && bp.unwrap() == address);
+            .zip(watchpoints)
+            .position(|(bp, wp)| wp.is_none() && *bp == Some(address));
 
         tracing::debug!(
             "Will clear HW breakpoint    #{} with comparator address    {:#08x}",
@@ -900,6 +1009,59 @@ impl<'probe> Core<'probe> {
         }
     }
 
+    /// Set a hardware watchpoint
+    ///
+    /// This function will try to set a hardware watchpoint on `length` bytes starting at
+    /// `address`, using one of the units which are otherwise used for breakpoints.
+    #[tracing::instrument(skip(self))]
+    pub fn set_hw_watchpoint(
+        &mut self,
+        address: u64,
+        length: u32,
+        kind: WatchKind,
+    ) -> Result<(), error::Error> {
+        if !self.inner.hw_breakpoints_enabled() {
+            self.enable_breakpoints(true)?;
+        }
+
+        let unit_index = self.find_free_breakpoint_comparator_index()?;
+
+        tracing::debug!(
+            "Trying to set HW watchpoint #{} with comparator address  {:#08x}",
+            unit_index,
+            address
+        );
+
+        self.inner
+            .set_hw_watchpoint(unit_index, address, length, kind)
+    }
+
+    /// Clear a hardware watchpoint
+    ///
+    /// This function will try to clear a hardware watchpoint at `address` if there exists a watchpoint at that address.
+    #[tracing::instrument(skip(self))]
+    pub fn clear_hw_watchpoint(&mut self, address: u64) -> Result<(), error::Error> {
+        let wp_position = self
+            .inner
+            .hw_watchpoints()?
+            .iter()
+            .position(|wp| *wp == Some(address));
+
+        tracing::debug!(
+            "Will clear HW watchpoint    #{} with comparator address    {:#08x}",
+            wp_position.unwrap_or(usize::MAX),
+            address
+        );
+
+        match wp_position {
+            Some(wp_position) => self.inner.clear_hw_breakpoint(wp_position),
+            None => Err(error::Error::Other(anyhow!(
+                "No watchpoint found at address {:#010x}",
+                address
+            ))),
+        }
+    }
+
     /// Clear all hardware breakpoints
     ///
     /// This function will clear all HW breakpoints which are configured on the target,
@@ -907,8 +1069,11 @@ impl<'probe> Core<'probe> {
     /// Also used as a helper function in [`Session::drop`](crate::session::Session).
     #[tracing::instrument(skip(self))]
     pub fn clear_all_hw_breakpoints(&mut self) -> Result<(), error::Error> {
-        for breakpoint in (self.inner.hw_breakpoints()?).into_iter().flatten() {
-            self.clear_hw_breakpoint(breakpoint)?
+        // Clear by unit, so that watchpoints in breakpoint units are cleared as well.
+        for (unit_index, breakpoint) in self.inner.hw_breakpoints()?.into_iter().enumerate() {
+            if breakpoint.is_some() {
+                self.inner.clear_hw_breakpoint(unit_index)?;
+            }
         }
         Ok(())
     }
@@ -1058,6 +1223,10 @@ impl<'probe> CoreInterface for Core<'probe> {
         todo!()
     }
 
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<u64>>, error::Error> {
+        self.inner.hw_watchpoints()
+    }
+
     fn enable_breakpoints(&mut self, state: bool) -> Result<(), error::Error> {
         self.enable_breakpoints(state)
     }
@@ -1070,6 +1239,16 @@ impl<'probe> CoreInterface for Core<'probe> {
         self.clear_all_hw_breakpoints()
     }
 
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        addr: u64,
+        length: u32,
+        kind: WatchKind,
+    ) -> Result<(), error::Error> {
+        self.inner.set_hw_watchpoint(unit_index, addr, length, kind)
+    }
+
     fn registers(&self) -> &'static registers::CoreRegisters {
         self.registers()
     }
@@ -1149,3 +1328,34 @@ impl std::fmt::Debug for ResolvedCoreOptions {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::architecture::arm::component::{WatchKind, WatchpointConfig};
+    use crate::test::{hit_dwt_comparator, session_with_dwt, set_dwt_comparator};
+
+    #[test]
+    fn watchpoint_hit_is_reported() {
+        let (mut session, registers) = session_with_dwt(2);
+        let mut core = session.core(0).unwrap();
+
+        core.halt(Duration::from_millis(100)).unwrap();
+        assert_eq!(core.on_watchpoint_hit().unwrap(), None);
+
+        // Comparator 1 matched a write to the word at 0x2000_0010.
+        set_dwt_comparator(&registers, 1, 0x2000_0010, 2, 0b0110);
+        hit_dwt_comparator(&registers, 1);
+
+        assert_eq!(
+            core.on_watchpoint_hit().unwrap(),
+            Some(WatchpointConfig {
+                unit: 1,
+                address: 0x2000_0010,
+                length: 4,
+                kind: WatchKind::Write,
+            })
+        );
+    }
+}
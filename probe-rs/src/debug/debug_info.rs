@@ -13,13 +13,13 @@ use gimli::{
     BaseAddresses, ColumnType, DebugFrame, FileEntry, LineProgramHeader, UnwindContext,
     UnwindSection,
 };
-use object::read::{Object, ObjectSection};
+use object::read::{Object, ObjectSection, ObjectSymbol};
 use probe_rs_target::InstructionSet;
 use typed_path::{TypedPath, TypedPathBuf};
 
 use std::{
-    borrow, cmp::Ordering, convert::TryInto, num::NonZeroU64, ops::ControlFlow, path::Path, rc::Rc,
-    str::from_utf8,
+    borrow, cmp::Ordering, collections::HashMap, convert::TryInto, num::NonZeroU64,
+    ops::ControlFlow, path::Path, rc::Rc, str::from_utf8,
 };
 
 pub(crate) type GimliReader = gimli::EndianReader<gimli::LittleEndian, std::rc::Rc<[u8]>>;
@@ -47,6 +47,8 @@ pub struct DebugInfo {
     pub(crate) locations_section: gimli::LocationLists<DwarfReader>,
     pub(crate) address_section: gimli::DebugAddr<DwarfReader>,
     pub(crate) debug_line_section: gimli::DebugLine<DwarfReader>,
+    /// The address and size of each named symbol in the symbol table of the ELF file.
+    pub(crate) symbols: HashMap<String, (u64, u64)>,
 }
 
 impl DebugInfo {
@@ -85,15 +87,34 @@ impl DebugInfo {
         let locations_section = gimli::LocationLists::new(debug_loc, debug_loc_lists);
         let debug_line_section = gimli::DebugLine::load(load_section)?;
 
+        let mut symbols = HashMap::new();
+        for symbol in object.symbols() {
+            if let Ok(name) = symbol.name() {
+                // Keep the first symbol, if a name occurs multiple times.
+                symbols
+                    .entry(name.to_string())
+                    .or_insert((symbol.address(), symbol.size()));
+            }
+        }
+
         Ok(DebugInfo {
             dwarf: dwarf_cow,
             frame_section,
             locations_section,
             address_section,
             debug_line_section,
+            symbols,
         })
     }
 
+    /// Get the address and size of the symbol `name` from the symbol table.
+    ///
+    /// The size is 0 for symbols without a known size, such as linker symbols.
+    /// If no symbol is found, `None` will be returned.
+    pub fn symbol_region(&self, name: &str) -> Option<(u64, u64)> {
+        self.symbols.get(name).copied()
+    }
+
     /// Get the name of the function at the given address.
     ///
     /// If no function is found, `None` will be returend.
@@ -1614,6 +1635,22 @@ mod test {
         }
     }
 
+    #[test]
+    fn symbol_region_is_read_from_symbol_table() {
+        let debug_info = load_test_elf_as_debug_info("gpio-hal-blinky/elf");
+
+        assert_eq!(
+            debug_info.symbol_region("DEVICE_PERIPHERALS"),
+            Some((0x2000_0000, 1))
+        );
+        // Linker symbols have no size.
+        assert_eq!(
+            debug_info.symbol_region("__ebss").map(|(_, size)| size),
+            Some(0)
+        );
+        assert_eq!(debug_info.symbol_region("NOT_A_SYMBOL"), None);
+    }
+
     #[test]
     fn unwinding_first_instruction_after_exception() {
         let debug_info = load_test_elf_as_debug_info("exceptions");
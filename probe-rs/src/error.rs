@@ -45,6 +45,9 @@ pub enum Error {
     /// the other architectures later.
     #[error("This capability has not yet been implemented for this architecture: {0}")]
     NotImplemented(&'static str),
+    /// All units which can be used for data watchpoints are in use.
+    #[error("No free units are available for data watchpoints")]
+    NoFreeWatchpointUnits,
     /// Any other error occurred.
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -0,0 +1,660 @@
+use super::{GdbErrorExt, RuntimeTarget};
+
+use crate::architecture::arm::{component::WatchKind, ArmError};
+use crate::config::MemoryRegion;
+use crate::{
+    Architecture, Core, Error, InstructionSet, MemoryInterface, Session, WatchpointRequest,
+};
+
+use gdbstub::target::ext::breakpoints::{
+    Breakpoints, HwBreakpoint, HwBreakpointOps, HwWatchpoint, HwWatchpointOps, SwBreakpoint,
+    SwBreakpointOps,
+};
+use gdbstub::target::TargetError;
+
+impl Breakpoints for RuntimeTarget<'_> {
+    fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_hw_breakpoint(&mut self) -> Option<HwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_hw_watchpoint(&mut self) -> Option<HwWatchpointOps<'_, Self>> {
+        if self.hw_watchpoint_support.is_none() {
+            self.hw_watchpoint_support = self.probe_hw_watchpoint_support();
+        }
+
+        // If probing failed, watchpoints are offered, so setting one reports the error.
+        match self.hw_watchpoint_support {
+            Some(false) => None,
+            _ => Some(self),
+        }
+    }
+}
+
+impl RuntimeTarget<'_> {
+    /// Returns whether all cores can set hardware watchpoints, or `None` if this can't be
+    /// determined.
+    fn probe_hw_watchpoint_support(&self) -> Option<bool> {
+        let mut session = self.session.lock().unwrap();
+
+        // Watchpoints use the DWT on ARM, and the trigger module on RISC-V.
+        if session.architecture() == Architecture::Riscv {
+            return Some(true);
+        }
+
+        for core_id in &self.cores {
+            match session.data_watchpoint_units(*core_id) {
+                Ok(0) => {
+                    tracing::info!(
+                        "Core {core_id} has no DWT comparators, watchpoints are disabled"
+                    );
+                    return Some(false);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!("Failed to find the DWT of core {core_id}: {e}");
+                    return None;
+                }
+            }
+        }
+
+        Some(true)
+    }
+}
+
+impl SwBreakpoint for RuntimeTarget<'_> {
+    fn add_sw_breakpoint(
+        &mut self,
+        addr: u64,
+        kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        if self.insert_breakpoint_instruction(addr, kind)? {
+            return Ok(true);
+        }
+
+        // Code outside of RAM, like in flash, is halted on with a breakpoint unit instead.
+        if !self.set_hw_breakpoint_units(addr)? {
+            tracing::warn!(
+                "Cannot set a breakpoint at {:#010x}, which is not in RAM, as all breakpoint units are in use",
+                addr
+            );
+            return Err(TargetError::NonFatal);
+        }
+
+        Ok(true)
+    }
+
+    fn remove_sw_breakpoint(
+        &mut self,
+        addr: u64,
+        _kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        let Some(original) = self.sw_breakpoints.get(&addr) else {
+            // The breakpoint may have been set in a breakpoint unit, see `add_sw_breakpoint`.
+            return self.clear_hw_breakpoint_units(addr);
+        };
+
+        let mut session = self.session.lock().unwrap();
+        let mut core = session.core(self.cores[0]).into_target_result()?;
+
+        core.write_8(addr, original)
+            .into_target_result_non_fatal()?;
+
+        self.sw_breakpoints.remove(&addr);
+
+        Ok(true)
+    }
+}
+
+impl RuntimeTarget<'_> {
+    /// Replaces the instruction at `addr` with a breakpoint instruction.
+    ///
+    /// Returns `false` if `addr` is not in RAM, as other memory can't be written
+    /// through the normal memory interface.
+    fn insert_breakpoint_instruction(
+        &mut self,
+        addr: u64,
+        kind: usize,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        if self.sw_breakpoints.contains_key(&addr) {
+            return Ok(true);
+        }
+
+        let mut session = self.session.lock().unwrap();
+
+        let mut core = session.core(self.cores[0]).into_target_result()?;
+        let instruction_set = core.instruction_set().into_target_result()?;
+        let instruction = breakpoint_instruction(instruction_set, kind);
+        drop(core);
+
+        // The breakpoint instruction can only be written to memory which is writable
+        // through the normal memory interface.
+        let writable = session
+            .target()
+            .memory_map
+            .iter()
+            .any(|region| match region {
+                MemoryRegion::Ram(ram) => {
+                    ram.range.start <= addr && addr + instruction.len() as u64 <= ram.range.end
+                }
+                _ => false,
+            });
+        if !writable {
+            return Ok(false);
+        }
+
+        let mut core = session.core(self.cores[0]).into_target_result()?;
+
+        let mut original = vec![0; instruction.len()];
+        core.read_8(addr, &mut original)
+            .into_target_result_non_fatal()?;
+        core.write_8(addr, instruction)
+            .into_target_result_non_fatal()?;
+
+        self.sw_breakpoints.insert(addr, original);
+
+        Ok(true)
+    }
+}
+
+/// Returns the encoding of the breakpoint instruction for `instruction_set`.
+///
+/// `kind` is the size of the replaced instruction as reported by GDB, which distinguishes
+/// Thumb from ARM code on cores supporting both.
+fn breakpoint_instruction(instruction_set: InstructionSet, kind: usize) -> &'static [u8] {
+    match instruction_set {
+        // BKPT #0
+        InstructionSet::Thumb2 => &[0x00, 0xbe],
+        // BKPT #0, or the Thumb BKPT #0 when replacing a 16-bit instruction
+        InstructionSet::A32 if kind == 2 => &[0x00, 0xbe],
+        InstructionSet::A32 => &[0x70, 0x00, 0x20, 0xe1],
+        // BRK #0
+        InstructionSet::A64 => &[0x00, 0x00, 0x20, 0xd4],
+        // C.EBREAK
+        InstructionSet::RV32C if kind == 2 => &[0x02, 0x90],
+        // EBREAK
+        InstructionSet::RV32 | InstructionSet::RV32C => &[0x73, 0x00, 0x10, 0x00],
+    }
+}
+
+impl RuntimeTarget<'_> {
+    /// Sets a breakpoint at `addr` in a breakpoint unit of every core.
+    ///
+    /// Returns `false` without changing any core if a core has no free unit.
+    fn set_hw_breakpoint_units(&mut self, addr: u64) -> gdbstub::target::TargetResult<bool, Self> {
+        let mut session = self.session.lock().unwrap();
+
+        // All cores are checked first, so that the breakpoint is never only set on some of them.
+        let mut units_free = true;
+        for core_id in &self.cores {
+            let mut core = session.core(*core_id).into_target_result()?;
+
+            if !core.can_set_hw_breakpoint(addr).into_target_result()? {
+                units_free = false;
+                break;
+            }
+        }
+
+        if !units_free {
+            return Ok(false);
+        }
+
+        apply_on_all_cores(
+            &mut session,
+            &self.cores,
+            |core| core.set_hw_breakpoint(addr),
+            |core| core.clear_hw_breakpoint(addr),
+        )
+        .into_target_result()?;
+
+        Ok(true)
+    }
+
+    /// Clears the breakpoint at `addr` from the breakpoint units of every core.
+    ///
+    /// Returns `false` if no breakpoint unit is set to `addr`.
+    fn clear_hw_breakpoint_units(
+        &mut self,
+        addr: u64,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        let mut session = self.session.lock().unwrap();
+
+        let mut core = session.core(self.cores[0]).into_target_result()?;
+        if !core
+            .list_hw_breakpoints()
+            .into_target_result()?
+            .contains(&Some(addr))
+        {
+            return Ok(false);
+        }
+        drop(core);
+
+        for core_id in &self.cores {
+            let mut core = session.core(*core_id).into_target_result()?;
+
+            core.clear_hw_breakpoint(addr).into_target_result()?;
+        }
+
+        Ok(true)
+    }
+}
+
+impl HwBreakpoint for RuntimeTarget<'_> {
+    fn add_hw_breakpoint(
+        &mut self,
+        addr: u64,
+        kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        if self.set_hw_breakpoint_units(addr)? {
+            return Ok(true);
+        }
+
+        // Once the breakpoint units of any core are used up, code in RAM can still be halted
+        // on with a breakpoint instruction. Otherwise the breakpoint is rejected.
+        self.insert_breakpoint_instruction(addr, kind)
+    }
+
+    fn remove_hw_breakpoint(
+        &mut self,
+        addr: u64,
+        kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        // The breakpoint may have been set as a breakpoint instruction, see `add_hw_breakpoint`.
+        if self.sw_breakpoints.contains_key(&addr) {
+            return self.remove_sw_breakpoint(addr, kind);
+        }
+
+        self.clear_hw_breakpoint_units(addr)
+    }
+}
+
+/// Calls `apply` for each of `cores`.
+///
+/// If it fails for any core, `undo` is called for the cores it succeeded for before, so that the
+/// cores are not left configured differently.
+fn apply_on_all_cores(
+    session: &mut Session,
+    cores: &[usize],
+    mut apply: impl FnMut(&mut Core) -> Result<(), Error>,
+    mut undo: impl FnMut(&mut Core) -> Result<(), Error>,
+) -> Result<(), Error> {
+    for (i, core_id) in cores.iter().enumerate() {
+        let result = session.core(*core_id).and_then(|mut core| apply(&mut core));
+
+        if let Err(error) = result {
+            for core_id in &cores[..i] {
+                let undone = session.core(*core_id).and_then(|mut core| undo(&mut core));
+                if let Err(e) = undone {
+                    tracing::warn!("Failed to restore the state of core {}: {}", core_id, e);
+                }
+            }
+
+            return Err(error);
+        }
+    }
+
+    Ok(())
+}
+
+impl HwWatchpoint for RuntimeTarget<'_> {
+    fn add_hw_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: gdbstub::target::ext::breakpoints::WatchKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        let length = len
+            .try_into()
+            .map_err(|_| Error::from(ArmError::InvalidWatchpointLength(len)))
+            .into_target_result_non_fatal()?;
+
+        let mut session = self.session.lock().unwrap();
+
+        // Every core has its own DWT or trigger module, so the watchpoint is set on each of them.
+        let mut handles = Vec::with_capacity(self.cores.len());
+        for core_id in &self.cores {
+            let request = WatchpointRequest {
+                core_index: *core_id,
+                address: addr,
+                length,
+                kind: kind.into(),
+            };
+
+            match session.add_watchpoint(request) {
+                Ok(handle) => handles.push(handle),
+                Err(e) => {
+                    for handle in handles {
+                        if let Err(e) = session.remove_watchpoint(handle) {
+                            tracing::warn!("Failed to remove partially set watchpoint: {e}");
+                        }
+                    }
+
+                    if let Error::NoFreeWatchpointUnits | Error::Arm(ArmError::NoFreeComparator) = e
+                    {
+                        tracing::warn!("No more watchpoints can be set, all units are in use");
+                        return Err(TargetError::NonFatal);
+                    }
+                    return Err(e).into_target_result_non_fatal();
+                }
+            }
+        }
+
+        self.hw_watchpoints
+            .insert((addr, len, kind.into()), handles);
+        Ok(true)
+    }
+
+    fn remove_hw_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: gdbstub::target::ext::breakpoints::WatchKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        // Only remove the exact watchpoint GDB set, not another one on the same address.
+        let key = (addr, len, kind.into());
+        let Some(handles) = self.hw_watchpoints.remove(&key) else {
+            return Ok(false);
+        };
+
+        let mut session = self.session.lock().unwrap();
+        let mut handles = handles.into_iter();
+        while let Some(handle) = handles.next() {
+            if let Err(e) = session.remove_watchpoint(handle) {
+                // Keep the watchpoints which are still set, so removing them can be retried.
+                self.hw_watchpoints
+                    .insert(key, std::iter::once(handle).chain(handles).collect());
+                return Err(e).into_target_result_non_fatal();
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// GDB access watchpoints (`awatch`) map to [`WatchKind::ReadWrite`].
+///
+/// The match has no wildcard arm, so a new kind in gdbstub fails to compile instead of being
+/// mapped silently.
+impl From<gdbstub::target::ext::breakpoints::WatchKind> for WatchKind {
+    fn from(kind: gdbstub::target::ext::breakpoints::WatchKind) -> Self {
+        match kind {
+            gdbstub::target::ext::breakpoints::WatchKind::Write => WatchKind::Write,
+            gdbstub::target::ext::breakpoints::WatchKind::Read => WatchKind::Read,
+            gdbstub::target::ext::breakpoints::WatchKind::ReadWrite => WatchKind::ReadWrite,
+        }
+    }
+}
+
+impl From<WatchKind> for gdbstub::target::ext::breakpoints::WatchKind {
+    fn from(kind: WatchKind) -> Self {
+        match kind {
+            WatchKind::Write => gdbstub::target::ext::breakpoints::WatchKind::Write,
+            WatchKind::Read => gdbstub::target::ext::breakpoints::WatchKind::Read,
+            WatchKind::ReadWrite => gdbstub::target::ext::breakpoints::WatchKind::ReadWrite,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use gdbstub::target::ext::breakpoints::{
+        Breakpoints, HwBreakpoint, HwWatchpoint, SwBreakpoint, WatchKind,
+    };
+    use gdbstub::target::TargetError;
+
+    use std::sync::Mutex;
+
+    use super::{apply_on_all_cores, breakpoint_instruction};
+    use crate::gdb_server::target::test::{attach, runtime_target};
+    use crate::probe::fake_probe::FakeProbe;
+    use crate::test::{dwt_comp_address, session_with_dwt, set_dwt_comparator};
+    use crate::watchpoint::WatchpointUnit;
+    use crate::{Error, InstructionSet, Permissions, WatchpointHandle};
+
+    #[test]
+    fn breakpoint_instruction_width() {
+        assert_eq!(
+            breakpoint_instruction(InstructionSet::Thumb2, 4),
+            &[0x00, 0xbe]
+        );
+        assert_eq!(
+            breakpoint_instruction(InstructionSet::A32, 2),
+            &[0x00, 0xbe]
+        );
+        assert_eq!(
+            breakpoint_instruction(InstructionSet::A32, 4),
+            &[0x70, 0x00, 0x20, 0xe1]
+        );
+        assert_eq!(
+            breakpoint_instruction(InstructionSet::RV32C, 2),
+            &[0x02, 0x90]
+        );
+        assert_eq!(
+            breakpoint_instruction(InstructionSet::RV32C, 4),
+            &[0x73, 0x00, 0x10, 0x00]
+        );
+    }
+
+    #[test]
+    fn watchpoint_interface_error_is_returned() {
+        let session = attach(FakeProbe::new());
+        let mut target = runtime_target(&session);
+
+        // The fake probe fails to read the AP information needed to find the DWT.
+        assert!(target
+            .add_hw_watchpoint(0x2000_0000, 4, WatchKind::Write)
+            .is_err());
+        // Nothing was added, so there is nothing to remove either.
+        assert!(matches!(
+            target.remove_hw_watchpoint(0x2000_0000, 4, WatchKind::Write),
+            Ok(false)
+        ));
+    }
+
+    #[test]
+    fn unknown_watchpoint_is_not_removed() {
+        let session = attach(FakeProbe::new());
+        let mut target = runtime_target(&session);
+
+        for kind in [WatchKind::Write, WatchKind::Read, WatchKind::ReadWrite] {
+            assert!(matches!(
+                target.remove_hw_watchpoint(0x2000_0000, 4, kind),
+                Ok(false)
+            ));
+        }
+    }
+
+    #[test]
+    fn watchpoint_support_is_cached() {
+        let session = attach(FakeProbe::new());
+        let mut target = runtime_target(&session);
+
+        // The fake probe can't find the DWT, so watchpoints are offered and probed again.
+        assert!(target.support_hw_watchpoint().is_some());
+        assert_eq!(target.hw_watchpoint_support, None);
+
+        target.hw_watchpoint_support = Some(false);
+        assert!(target.support_hw_watchpoint().is_none());
+    }
+
+    #[test]
+    fn hw_breakpoint_limit_exceeded() {
+        let session = attach(FakeProbe::new());
+        let mut target = runtime_target(&session);
+
+        let available = session
+            .lock()
+            .unwrap()
+            .core(0)
+            .unwrap()
+            .available_breakpoint_units()
+            .unwrap();
+
+        // Breakpoints beyond the supported number are rejected, so GDB uses software breakpoints.
+        for i in 0..=available as u64 {
+            let added = target.add_hw_breakpoint(0x1000 + 2 * i, 2);
+            assert!(matches!(added, Ok(added) if added == (i < available as u64)));
+        }
+    }
+
+    #[test]
+    fn hw_breakpoint_falls_back_to_ram_breakpoint() {
+        let session = attach(FakeProbe::new());
+        let mut target = runtime_target(&session);
+
+        let available = session
+            .lock()
+            .unwrap()
+            .core(0)
+            .unwrap()
+            .available_breakpoint_units()
+            .unwrap();
+        for i in 0..available as u64 {
+            assert!(matches!(
+                target.add_hw_breakpoint(0x1000 + 2 * i, 2),
+                Ok(true)
+            ));
+        }
+
+        // A breakpoint instruction is written to RAM instead.
+        assert!(matches!(target.add_hw_breakpoint(0x2000_0000, 2), Ok(true)));
+        assert!(target.sw_breakpoints.contains_key(&0x2000_0000));
+
+        assert!(matches!(
+            target.remove_hw_breakpoint(0x2000_0000, 2),
+            Ok(true)
+        ));
+        assert!(target.sw_breakpoints.is_empty());
+    }
+
+    #[test]
+    fn sw_breakpoint_in_flash_uses_breakpoint_unit() {
+        // FP_CTRL with two code comparators
+        let session = attach(FakeProbe::with_mocked_core_registers([(
+            0xE000_2000,
+            2 << 4,
+        )]));
+        let mut target = runtime_target(&session);
+
+        // Flash can't be written with a breakpoint instruction, so the units are used.
+        assert!(matches!(target.add_sw_breakpoint(0x1000, 2), Ok(true)));
+        assert!(matches!(target.add_sw_breakpoint(0x1002, 2), Ok(true)));
+        assert!(target.sw_breakpoints.is_empty());
+        assert!(matches!(
+            target.add_sw_breakpoint(0x2000, 2),
+            Err(TargetError::NonFatal)
+        ));
+
+        assert!(matches!(target.remove_sw_breakpoint(0x1000, 2), Ok(true)));
+        assert!(matches!(target.remove_sw_breakpoint(0x1000, 2), Ok(false)));
+        assert!(matches!(target.add_sw_breakpoint(0x2000, 2), Ok(true)));
+
+        assert_eq!(
+            session
+                .lock()
+                .unwrap()
+                .core(0)
+                .unwrap()
+                .list_hw_breakpoints()
+                .unwrap(),
+            vec![Some(0x2000), Some(0x1002)]
+        );
+    }
+
+    #[test]
+    fn partial_breakpoint_is_rolled_back() {
+        let mut session = FakeProbe::new()
+            .into_probe()
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap();
+
+        let available = session.core(0).unwrap().free_breakpoint_units().unwrap();
+
+        // Fail on the second core, after the breakpoint was set on the first one.
+        let mut calls = 0;
+        let result = apply_on_all_cores(
+            &mut session,
+            &[0, 0],
+            |core| {
+                calls += 1;
+                if calls == 2 {
+                    return Err(Error::Other(anyhow::anyhow!("Injected failure")));
+                }
+                core.set_hw_breakpoint(0x1000)
+            },
+            |core| core.clear_hw_breakpoint(0x1000),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(
+            session.core(0).unwrap().free_breakpoint_units().unwrap(),
+            available
+        );
+    }
+
+    #[test]
+    fn watch_kind_mapping() {
+        use crate::architecture::arm::component::WatchKind as DwtWatchKind;
+
+        // Z2, Z3 and Z4 packets
+        for (gdb, dwt) in [
+            (WatchKind::Write, DwtWatchKind::Write),
+            (WatchKind::Read, DwtWatchKind::Read),
+            (WatchKind::ReadWrite, DwtWatchKind::ReadWrite),
+        ] {
+            assert_eq!(DwtWatchKind::from(gdb), dwt);
+            assert_eq!(WatchKind::from(dwt), gdb);
+        }
+    }
+
+    #[test]
+    fn watchpoints_are_cleared_on_disconnect() {
+        let session = attach(FakeProbe::new());
+        let mut target = runtime_target(&session);
+
+        let handle = WatchpointHandle {
+            core_index: 0,
+            address: 0x2000_0000,
+            unit: WatchpointUnit::Dwt(1),
+        };
+        target
+            .hw_watchpoints
+            .insert((0x2000_0000, 4, WatchKind::Write.into()), vec![handle]);
+
+        // The fake probe has no DWT, so removing the watchpoint fails, but it is forgotten.
+        target.clear_hw_watchpoints();
+        assert!(target.hw_watchpoints.is_empty());
+        assert!(matches!(
+            target.remove_hw_watchpoint(0x2000_0000, 4, WatchKind::Write),
+            Ok(false)
+        ));
+    }
+
+    #[test]
+    fn failed_watchpoint_removal_keeps_remaining_handles() {
+        let (session, registers) = session_with_dwt(2);
+        let session = Mutex::new(session);
+        let mut target = runtime_target(&session);
+
+        set_dwt_comparator(&registers, 0, 0x2000_0000, 2, 0b0110);
+
+        // Unit 5 doesn't exist, so removing it fails after unit 0 was removed.
+        let handles = [0, 5, 1].map(|unit| WatchpointHandle {
+            core_index: 0,
+            address: 0x2000_0000,
+            unit: WatchpointUnit::Dwt(unit),
+        });
+        let key = (0x2000_0000, 4, WatchKind::Write.into());
+        target.hw_watchpoints.insert(key, handles.to_vec());
+
+        assert!(target
+            .remove_hw_watchpoint(0x2000_0000, 4, WatchKind::Write)
+            .is_err());
+        // FUNCTION of unit 0
+        assert_eq!(registers.read(dwt_comp_address(0) + 8), 0);
+        assert_eq!(target.hw_watchpoints[&key], handles[1..]);
+    }
+}
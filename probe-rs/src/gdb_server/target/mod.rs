@@ -0,0 +1,623 @@
+mod base;
+mod breakpoints;
+mod desc;
+mod monitor;
+mod resume;
+mod thread;
+mod traits;
+mod utils;
+
+use super::arch::RuntimeArch;
+use crate::architecture::arm::component::{WatchKind, WatchpointConfig};
+use crate::{BreakpointCause, CoreStatus, Error, HaltReason, Session, WatchpointHandle};
+use gdbstub::stub::state_machine::GdbStubStateMachine;
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use gdbstub::common::Signal;
+use gdbstub::conn::ConnectionExt;
+use gdbstub::stub::{GdbStub, MultiThreadStopReason};
+use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::breakpoints::BreakpointsOps;
+use gdbstub::target::ext::memory_map::MemoryMapOps;
+use gdbstub::target::ext::monitor_cmd::MonitorCmdOps;
+use gdbstub::target::ext::target_description_xml_override::TargetDescriptionXmlOverrideOps;
+use gdbstub::target::Target;
+
+pub(crate) use traits::{GdbErrorExt, ProbeRsErrorExt};
+
+use desc::TargetDescription;
+
+/// Actions for resuming a core
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum ResumeAction {
+    /// Don't change the state
+    Unchanged,
+    /// Resume core
+    Resume,
+    /// Single step core
+    Step,
+}
+
+/// The top level gdbstub target for a probe-rs debug session
+pub(crate) struct RuntimeTarget<'a> {
+    /// The probe-rs session object
+    session: &'a Mutex<Session>,
+    /// A list of core IDs for this stub
+    cores: Vec<usize>,
+
+    /// TCP listener accepting incoming connections
+    listener: TcpListener,
+    /// The current GDB stub state machine
+    gdb: Option<GdbStubStateMachine<'a, RuntimeTarget<'a>, TcpStream>>,
+    /// Resume action to be used upon a continue request
+    resume_action: (usize, ResumeAction),
+
+    /// Description of target's architecture and registers
+    target_desc: TargetDescription,
+
+    /// Original instruction bytes replaced by software breakpoints, keyed by address
+    sw_breakpoints: HashMap<u64, Vec<u8>>,
+    /// Handles of the hardware watchpoints set by GDB on each core, keyed by address, length and kind
+    hw_watchpoints: HashMap<(u64, u64, WatchKind), Vec<WatchpointHandle>>,
+    /// Whether the cores support hardware watchpoints, once this has been determined
+    hw_watchpoint_support: Option<bool>,
+}
+
+impl<'a> RuntimeTarget<'a> {
+    /// Create a new RuntimeTarget and get ready to start processing GDB input
+    pub fn new(
+        session: &'a Mutex<Session>,
+        cores: Vec<usize>,
+        addrs: &[SocketAddr],
+    ) -> Result<Self, Error> {
+        let listener = TcpListener::bind(addrs).into_error()?;
+        listener.set_nonblocking(true).into_error()?;
+
+        Ok(Self {
+            session,
+            cores,
+            listener,
+            gdb: None,
+            resume_action: (0, ResumeAction::Unchanged),
+            target_desc: TargetDescription::default(),
+            sw_breakpoints: HashMap::new(),
+            hw_watchpoints: HashMap::new(),
+            hw_watchpoint_support: None,
+        })
+    }
+
+    /// Process any pending work for this target
+    ///
+    /// Returns: Duration to wait before processing this target again
+    pub fn process(&mut self) -> Result<Duration, Error> {
+        // State 1 - unconnected
+        if self.gdb.is_none() {
+            // See if we have a connection
+            match self.listener.accept() {
+                Ok((s, addr)) => {
+                    tracing::info!("New connection from {:#?}", addr);
+
+                    for i in 0..self.cores.len() {
+                        let core_id = self.cores[i];
+                        // When we first attach to the core, GDB expects us to halt the core, so we do this here when a new client connects.
+                        // If the core is already halted, nothing happens if we issue a halt command again, so we always do this no matter of core state.
+                        self.session
+                            .lock()
+                            .unwrap()
+                            .core(core_id)?
+                            .halt(Duration::from_millis(100))?;
+
+                        self.load_target_desc()?;
+                    }
+
+                    // Start the GDB Stub state machine
+                    let stub = GdbStub::<RuntimeTarget, _>::new(s);
+                    match stub.run_state_machine(self) {
+                        Ok(gdbstub) => {
+                            self.gdb = Some(gdbstub);
+                        }
+                        Err(e) => {
+                            // Any errors at this state are either IO errors or fatal config errors
+                            return Err(anyhow::Error::from(e).into());
+                        }
+                    };
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    // No connection yet
+                    return Ok(Duration::from_millis(10));
+                }
+                Err(e) => {
+                    // Fatal error
+                    return Err(anyhow::Error::from(e).into());
+                }
+            };
+        }
+
+        // Stage 2 - connected
+        if self.gdb.is_some() {
+            let mut wait_time = Duration::ZERO;
+            let gdb = self.gdb.take().unwrap();
+
+            self.gdb = match gdb {
+                GdbStubStateMachine::Idle(mut state) => {
+                    // Read data if available
+                    let next_byte = {
+                        let conn = state.borrow_conn();
+
+                        read_if_available(conn)?
+                    };
+
+                    if let Some(b) = next_byte {
+                        Some(state.incoming_data(self, b).into_error()?)
+                    } else {
+                        wait_time = Duration::from_millis(10);
+                        Some(state.into())
+                    }
+                }
+                GdbStubStateMachine::Running(mut state) => {
+                    // Read data if available
+                    let next_byte = {
+                        let conn = state.borrow_conn();
+
+                        read_if_available(conn)?
+                    };
+
+                    if let Some(b) = next_byte {
+                        Some(state.incoming_data(self, b).into_error()?)
+                    } else {
+                        // Check for break
+                        let mut stop_reason: Option<MultiThreadStopReason<u64>> = None;
+                        {
+                            let mut session = self.session.lock().unwrap();
+
+                            for i in &self.cores {
+                                let mut core = session.core(*i)?;
+                                let status = core.status()?;
+
+                                if let CoreStatus::Halted(reason) = status {
+                                    let tid = NonZeroUsize::new(i + 1).unwrap();
+                                    stop_reason = Some(halt_stop_reason(tid, reason, || {
+                                        core.matched_data_watchpoint()
+                                    }));
+                                    break;
+                                }
+                            }
+
+                            // halt all remaining cores that are still running
+                            // GDB expects all or nothing stops
+                            if stop_reason.is_some() {
+                                for i in &self.cores {
+                                    let mut core = session.core(*i)?;
+                                    if !core.core_halted()? {
+                                        core.halt(Duration::from_millis(100))?;
+                                    }
+                                }
+                            }
+                        }
+
+                        if let Some(reason) = stop_reason {
+                            Some(state.report_stop(self, reason).into_error()?)
+                        } else {
+                            wait_time = Duration::from_millis(10);
+                            Some(state.into())
+                        }
+                    }
+                }
+                GdbStubStateMachine::CtrlCInterrupt(state) => {
+                    // Break core, handle interrupt
+                    {
+                        let mut session = self.session.lock().unwrap();
+                        for i in &self.cores {
+                            let mut core = session.core(*i)?;
+
+                            core.halt(Duration::from_millis(100))?;
+                        }
+                    }
+
+                    Some(
+                        state
+                            .interrupt_handled(
+                                self,
+                                Some(MultiThreadStopReason::Signal(Signal::SIGINT)),
+                            )
+                            .into_error()?,
+                    )
+                }
+                GdbStubStateMachine::Disconnected(state) => {
+                    tracing::info!("GDB client disconnected: {:?}", state.get_reason());
+                    self.clear_hw_watchpoints();
+
+                    None
+                }
+            };
+
+            return Ok(wait_time);
+        }
+
+        Ok(Duration::ZERO)
+    }
+
+    /// Removes the hardware watchpoints set by GDB, which can't be removed by the client anymore
+    /// once it disconnected.
+    ///
+    /// Failures are logged, and the watchpoints are forgotten in any case.
+    fn clear_hw_watchpoints(&mut self) {
+        let mut session = self.session.lock().unwrap();
+
+        for (_, handles) in self.hw_watchpoints.drain() {
+            for handle in handles {
+                if let Err(e) = session.remove_watchpoint(handle) {
+                    tracing::warn!(
+                        "Failed to remove the watchpoint at {:#010x} of core {}: {}",
+                        handle.address(),
+                        handle.core_index(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Target for RuntimeTarget<'_> {
+    type Arch = RuntimeArch;
+    type Error = Error;
+
+    fn base_ops(&mut self) -> BaseOps<'_, Self::Arch, Self::Error> {
+        BaseOps::MultiThread(self)
+    }
+
+    fn support_target_description_xml_override(
+        &mut self,
+    ) -> Option<TargetDescriptionXmlOverrideOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_memory_map(&mut self) -> Option<MemoryMapOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_monitor_cmd(&mut self) -> Option<MonitorCmdOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn guard_rail_implicit_sw_breakpoints(&self) -> bool {
+        true
+    }
+}
+
+/// Read a byte from a stream if available, otherwise return None
+fn read_if_available(conn: &mut TcpStream) -> Result<Option<u8>, Error> {
+    match conn.peek() {
+        Ok(p) => {
+            // Unwrap is safe because peek already showed
+            // there's data in the buffer
+            match p {
+                Some(_) => conn.read().map(Some).into_error(),
+                None => Ok(None),
+            }
+        }
+        Err(e) => Err(anyhow::Error::from(e).into()),
+    }
+}
+
+/// Returns the stop reason reported to GDB for thread `tid`, which halted because of `reason`.
+///
+/// `matched_watchpoint` is only called for watchpoint halts, to report the address GDB watched.
+fn halt_stop_reason(
+    tid: NonZeroUsize,
+    reason: HaltReason,
+    matched_watchpoint: impl FnOnce() -> Result<Option<WatchpointConfig>, Error>,
+) -> MultiThreadStopReason<u64> {
+    match reason {
+        HaltReason::Breakpoint(BreakpointCause::Hardware)
+        | HaltReason::Breakpoint(BreakpointCause::Unknown) => {
+            // Some architectures do not allow us to distinguish between hardware and software breakpoints, so we just treat `Unknown` as hardware breakpoints.
+            MultiThreadStopReason::HwBreak(tid)
+        }
+        HaltReason::Step => MultiThreadStopReason::DoneStep,
+        HaltReason::Watchpoint => {
+            // Report the triggering address if the DWT tells us which comparator matched.
+            match matched_watchpoint() {
+                Ok(Some(watchpoint)) => MultiThreadStopReason::Watch {
+                    tid,
+                    kind: watchpoint.kind.into(),
+                    addr: watchpoint.address as u64,
+                },
+                _ => MultiThreadStopReason::SignalWithThread {
+                    tid,
+                    signal: Signal::SIGTRAP,
+                },
+            }
+        }
+        _ => MultiThreadStopReason::SignalWithThread {
+            tid,
+            signal: Signal::SIGINT,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::TcpStream;
+    use std::num::NonZeroUsize;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    use gdbstub::common::Signal;
+    use gdbstub::stub::MultiThreadStopReason;
+    use gdbstub::target::ext::breakpoints::WatchKind;
+
+    use super::{halt_stop_reason, RuntimeTarget};
+    use crate::probe::fake_probe::{FakeProbe, MockedRegisters};
+    use crate::test::{dwt_comp_address, hit_dwt_comparator, session_with_dwt, set_dwt_comparator};
+    use crate::{Error, HaltReason, Permissions, Session};
+
+    /// Attaches to the nRF51 behind `probe`, to share the session with a [`RuntimeTarget`].
+    pub(super) fn attach(probe: FakeProbe) -> Mutex<Session> {
+        let session = probe
+            .into_probe()
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap();
+
+        Mutex::new(session)
+    }
+
+    /// Attaches to the FE310 behind `probe`, which mocks a RISC-V hart.
+    fn attach_riscv(probe: FakeProbe) -> Mutex<Session> {
+        let session = probe
+            .into_probe()
+            .attach("fe310-g002", Permissions::default())
+            .unwrap();
+
+        Mutex::new(session)
+    }
+
+    /// Creates a target for core 0 of `session`, listening on a free local port.
+    pub(super) fn runtime_target(session: &Mutex<Session>) -> RuntimeTarget<'_> {
+        RuntimeTarget::new(session, vec![0], &["127.0.0.1:0".parse().unwrap()]).unwrap()
+    }
+
+    /// Connects a GDB client to `target`, which sends every packet without delay.
+    fn connect(target: &RuntimeTarget) -> TcpStream {
+        let client = TcpStream::connect(target.listener.local_addr().unwrap()).unwrap();
+        client.set_nodelay(true).unwrap();
+        client.set_nonblocking(true).unwrap();
+        client
+    }
+
+    /// Sends the GDB packet `data` to the stub.
+    fn send_packet(client: &mut TcpStream, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        client
+            .write_all(format!("${data}#{checksum:02x}").as_bytes())
+            .unwrap();
+    }
+
+    /// Expands the run-length encoding of packet data, where `*` followed by a character `n`
+    /// repeats the previous character `n - 29` times.
+    fn run_length_decode(data: &str) -> String {
+        let mut decoded = String::new();
+        let mut chars = data.chars();
+
+        while let Some(c) = chars.next() {
+            match (c, decoded.chars().last()) {
+                ('*', Some(previous)) => {
+                    let count = chars.next().unwrap() as usize - 29;
+                    decoded.extend(std::iter::repeat(previous).take(count));
+                }
+                _ => decoded.push(c),
+            }
+        }
+
+        decoded
+    }
+
+    /// Processes `target` for `duration`, or until the stub replied with a packet if
+    /// `wait_for_reply` is set, and returns the data of the reply.
+    fn process_for(
+        target: &mut RuntimeTarget,
+        client: &mut TcpStream,
+        duration: Duration,
+        wait_for_reply: bool,
+    ) -> Option<String> {
+        let deadline = Instant::now() + duration;
+        let mut received = Vec::new();
+
+        while Instant::now() < deadline {
+            std::thread::sleep(target.process().unwrap());
+
+            let mut buffer = [0; 256];
+            match client.read(&mut buffer) {
+                Ok(count) => received.extend_from_slice(&buffer[..count]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => panic!("Failed to read from the stub: {e}"),
+            }
+
+            // A packet is `$data#checksum`, possibly preceded by the acknowledgement `+`.
+            let text = String::from_utf8_lossy(&received);
+            if let Some((data, checksum)) = text
+                .split_once('$')
+                .and_then(|(_, packet)| packet.split_once('#'))
+            {
+                if checksum.len() >= 2 {
+                    client.write_all(b"+").unwrap();
+                    return Some(run_length_decode(data));
+                }
+            }
+        }
+
+        assert!(
+            !wait_for_reply,
+            "The stub did not reply in time: {:?}",
+            String::from_utf8_lossy(&received)
+        );
+        None
+    }
+
+    /// Creates a session whose Cortex-M core has a DWT with two comparators. Comparator 1
+    /// watches `0x2000_0010` with `function`.
+    fn session_watching(function: u32) -> (Session, MockedRegisters) {
+        let (session, registers) = session_with_dwt(2);
+        set_dwt_comparator(&registers, 0, 0x2000_0000, 0, 0b0110);
+        set_dwt_comparator(&registers, 1, 0x2000_0010, 2, function);
+
+        (session, registers)
+    }
+
+    #[test]
+    fn watchpoint_hit_is_reported() {
+        let tid = NonZeroUsize::new(1).unwrap();
+
+        for (function, expected) in [
+            (0b0101, WatchKind::Read),
+            (0b0110, WatchKind::Write),
+            (0b0111, WatchKind::ReadWrite),
+        ] {
+            let (mut session, registers) = session_watching(function);
+            hit_dwt_comparator(&registers, 1);
+            let reason = halt_stop_reason(tid, HaltReason::Watchpoint, || {
+                session.core(0)?.matched_data_watchpoint()
+            });
+
+            assert!(matches!(
+                reason,
+                MultiThreadStopReason::Watch { tid: thread, kind, addr: 0x2000_0010 }
+                    if thread == tid && kind == expected
+            ));
+        }
+    }
+
+    #[test]
+    fn unknown_watchpoint_hit_is_a_trap() {
+        let tid = NonZeroUsize::new(2).unwrap();
+
+        // No comparator has the MATCHED flag set.
+        let (mut session, _) = session_watching(0b0110);
+        let reason = halt_stop_reason(tid, HaltReason::Watchpoint, || {
+            session.core(0)?.matched_data_watchpoint()
+        });
+        assert!(matches!(
+            reason,
+            MultiThreadStopReason::SignalWithThread {
+                signal: Signal::SIGTRAP,
+                ..
+            }
+        ));
+
+        let reason = halt_stop_reason(tid, HaltReason::Watchpoint, || {
+            Err(Error::NotImplemented("reporting matched data watchpoints"))
+        });
+        assert!(matches!(
+            reason,
+            MultiThreadStopReason::SignalWithThread {
+                signal: Signal::SIGTRAP,
+                ..
+            }
+        ));
+
+        // Other halts don't read the DWT.
+        let reason = halt_stop_reason(tid, HaltReason::Request, || unreachable!());
+        assert!(matches!(
+            reason,
+            MultiThreadStopReason::SignalWithThread {
+                signal: Signal::SIGINT,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn watchpoint_hit_is_reported_to_client() {
+        let (session, registers) = session_with_dwt(2);
+        let session = Mutex::new(session);
+
+        let mut target = runtime_target(&session);
+        let mut client = connect(&target);
+
+        send_packet(&mut client, "Z2,20000010,4");
+        let reply = process_for(&mut target, &mut client, Duration::from_secs(5), true);
+        assert_eq!(reply.as_deref(), Some("OK"));
+
+        // Find the comparator GDB's watchpoint was set on.
+        let unit = (0..2)
+            .find(|&unit| registers.read(dwt_comp_address(unit)) == 0x2000_0010)
+            .unwrap();
+
+        // The core keeps running until the watchpoint is hit.
+        send_packet(&mut client, "c");
+        let reply = process_for(&mut target, &mut client, Duration::from_millis(100), false);
+        assert_eq!(reply, None);
+
+        // The comparator matches a write, and the DWT halts the core.
+        hit_dwt_comparator(&registers, unit);
+
+        let reply = process_for(&mut target, &mut client, Duration::from_secs(5), true).unwrap();
+        assert!(reply.starts_with("T05"), "{reply}");
+        assert!(reply.contains("watch:20000010;"), "{reply}");
+    }
+
+    /// Returns the `tdata1` and `tdata2` registers of trigger `unit` of the RISC-V hart.
+    fn riscv_trigger(session: &Mutex<Session>, unit: u32) -> (u32, u32) {
+        let mut session = session.lock().unwrap();
+        let mut core = session.core(0).unwrap();
+        core.write_core_reg(0x7a0, unit).unwrap();
+
+        (
+            core.read_core_reg(0x7a1).unwrap(),
+            core.read_core_reg(0x7a2).unwrap(),
+        )
+    }
+
+    #[test]
+    fn riscv_watchpoint_matches_requested_length() {
+        // Store trigger in M and U mode, which enters debug mode.
+        let store = 1 << 27 | 1 << 12 | 1 << 6 | 1 << 3 | 1 << 1;
+
+        for (maskmax, tdata1, tdata2) in [
+            // The 4 bytes are matched as a NAPOT region.
+            (4, 0x2080_0000 | store | 1 << 7, 0x2000_0011),
+            // Without NAPOT support, only the first byte is matched.
+            (0, 0x2000_0000 | store, 0x2000_0010),
+        ] {
+            let session = attach_riscv(FakeProbe::with_mocked_riscv_core(2, maskmax));
+            let mut target = runtime_target(&session);
+            let mut client = connect(&target);
+
+            send_packet(&mut client, "Z2,20000010,4");
+            let reply = process_for(&mut target, &mut client, Duration::from_secs(5), true);
+            assert_eq!(reply.as_deref(), Some("OK"));
+            assert_eq!(riscv_trigger(&session, 0), (tdata1, tdata2));
+
+            // The watchpoint is found by its start address, and removed.
+            send_packet(&mut client, "z2,20000010,4");
+            let reply = process_for(&mut target, &mut client, Duration::from_secs(5), true);
+            assert_eq!(reply.as_deref(), Some("OK"));
+            assert_eq!(riscv_trigger(&session, 0).1, 0);
+        }
+    }
+
+    #[test]
+    fn riscv_watchpoint_must_be_naturally_aligned() {
+        let session = attach_riscv(FakeProbe::with_mocked_riscv_core(2, 4));
+        let mut target = runtime_target(&session);
+        let mut client = connect(&target);
+
+        for packet in ["Z2,20000012,4", "Z2,20000010,3"] {
+            send_packet(&mut client, packet);
+            let reply = process_for(&mut target, &mut client, Duration::from_secs(5), true);
+            assert!(reply.unwrap().starts_with('E'), "{packet}");
+        }
+        assert_eq!(riscv_trigger(&session, 0), (0x2080_0000, 0));
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use super::RuntimeTarget;
+
+use gdbstub::target::ext::monitor_cmd::outputln;
+use gdbstub::target::ext::monitor_cmd::MonitorCmd;
+
+const HELP_TEXT: &str = r#"Supported Commands:
+
+    info - print session information
+    breakpoints - list the hardware breakpoint units in use
+    watchpoints - list the DWT comparators in use as watchpoints
+    dwt reset - remove all watchpoints and reset the DWT comparators and counters
+    reset - reset target
+    reset halt - reset target and halt afterwards
+"#;
+
+impl MonitorCmd for RuntimeTarget<'_> {
+    fn handle_monitor_cmd(
+        &mut self,
+        cmd: &[u8],
+        mut out: gdbstub::target::ext::monitor_cmd::ConsoleOutput<'_>,
+    ) -> Result<(), Self::Error> {
+        let cmd = String::from_utf8_lossy(cmd);
+
+        match cmd.as_ref() {
+            "info" => {
+                outputln!(
+                    out,
+                    "Target info:\n\n{:#?}",
+                    self.session.lock().unwrap().target()
+                );
+            }
+            "breakpoints" => {
+                let mut session = self.session.lock().unwrap();
+                for core_id in &self.cores {
+                    let breakpoints = session.core(*core_id)?.list_hw_breakpoints()?;
+                    let used = breakpoints.iter().flatten().count();
+                    outputln!(
+                        out,
+                        "Core {}: {} of {} breakpoint units in use",
+                        core_id,
+                        used,
+                        breakpoints.len()
+                    );
+
+                    for (unit, address) in breakpoints.iter().enumerate() {
+                        if let Some(address) = address {
+                            outputln!(out, "    unit {}: {:#010x}", unit, address);
+                        }
+                    }
+                }
+            }
+            "watchpoints" => {
+                let mut session = self.session.lock().unwrap();
+                for core_id in &self.cores {
+                    let mut core = session.core(*core_id)?;
+                    let watchpoints = match core.list_data_watchpoints() {
+                        Ok(watchpoints) => watchpoints,
+                        Err(e) => {
+                            outputln!(out, "Core {}: {}", core_id, e);
+                            continue;
+                        }
+                    };
+                    let free = core.free_watchpoint_units()?;
+                    outputln!(
+                        out,
+                        "Core {}: {} watchpoints, {} comparators free",
+                        core_id,
+                        watchpoints.len(),
+                        free
+                    );
+
+                    for watchpoint in watchpoints {
+                        outputln!(
+                            out,
+                            "    unit {}: {} {:#010x} ({} bytes)",
+                            watchpoint.unit,
+                            watchpoint.kind,
+                            watchpoint.address,
+                            watchpoint.length
+                        );
+                    }
+                }
+            }
+            "dwt reset" => {
+                let mut session = self.session.lock().unwrap();
+                for core_id in &self.cores {
+                    match session.reset_dwt(*core_id) {
+                        Ok(()) => outputln!(out, "Core {}: DWT reset", core_id),
+                        Err(e) => {
+                            outputln!(out, "Core {}: Error while resetting DWT:\n\t{}", core_id, e)
+                        }
+                    }
+                }
+                // The watchpoints can't be removed by GDB anymore.
+                self.hw_watchpoints.clear();
+            }
+            "reset" => {
+                outputln!(out, "Resetting target");
+                match self.session.lock().unwrap().core(0)?.reset() {
+                    Ok(_) => {
+                        outputln!(out, "Done")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while resetting target:\n\t{}", e)
+                    }
+                }
+            }
+            "reset halt" => {
+                let timeout: Duration = Duration::new(1, 0);
+                outputln!(out, "Resetting and halting target");
+                match self
+                    .session
+                    .lock()
+                    .unwrap()
+                    .core(0)?
+                    .reset_and_halt(timeout)
+                {
+                    Ok(_) => {
+                        outputln!(out, "Target halted")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while halting target:\n\t{}", e)
+                    }
+                }
+            }
+            _ => {
+                outputln!(out, "{}", HELP_TEXT);
+            }
+        }
+
+        Ok(())
+    }
+}
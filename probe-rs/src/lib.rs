@@ -91,6 +91,8 @@ pub mod rtt;
 mod session;
 #[cfg(test)]
 mod test;
+#[warn(missing_docs)]
+mod watchpoint;
 
 pub use crate::config::{CoreType, InstructionSet, Target};
 pub use crate::core::{
@@ -106,9 +108,12 @@ pub use crate::probe::{
     DebugProbeSelector, DebugProbeType, Probe, ProbeCreationError, WireProtocol,
 };
 pub use crate::session::{Permissions, Session};
+pub use crate::watchpoint::{WatchpointHandle, WatchpointRequest};
 
 // Exports only used in tests
 #[cfg(feature = "test")]
+pub use crate::probe::fake_probe::MockedRegisters;
+#[cfg(feature = "test")]
 pub use crate::probe::fake_probe::Operation as ProbeOperation;
 #[cfg(feature = "test")]
 pub use crate::probe::list::ProbeLister;
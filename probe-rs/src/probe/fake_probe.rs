@@ -1,5 +1,13 @@
 #![allow(missing_docs)] // Don't require docs for test code
-use std::{cell::RefCell, collections::VecDeque, fmt::Debug, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use probe_rs_target::ScanChainElement;
 
@@ -13,9 +21,11 @@ use crate::{
         dp::DebugPortError,
         memory::adi_v5_memory_interface::{ADIMemoryInterface, ArmProbe},
         sequences::ArmDebugSequence,
-        ApAddress, ArmError, ArmProbeInterface, DapAccess, DpAddress, MemoryApInformation,
-        PortType, RawDapAccess, SwoAccess,
+        ApAddress, ApInformation, ArmError, ArmProbeInterface, DapAccess, DpAddress,
+        MemoryApInformation, PortType, RawDapAccess, SwoAccess,
     },
+    architecture::riscv::communication_interface::{RiscvCommunicationInterface, RiscvError},
+    probe::JTAGAccess,
     DebugProbe, DebugProbeError, DebugProbeSelector, Error, MemoryMappedRegister, Probe,
     WireProtocol,
 };
@@ -35,6 +45,12 @@ pub struct FakeProbe {
     operations: RefCell<VecDeque<Operation>>,
 
     memory_ap: MockedAp,
+
+    /// Base address of the CoreSight component behind the memory AP, if it is reported
+    debug_base_address: Option<u64>,
+
+    /// A RISC-V hart behind the JTAG debug transport module, if one is mocked
+    riscv_core: Option<MockRiscvCore>,
 }
 
 enum MockedAp {
@@ -44,18 +60,71 @@ enum MockedAp {
     Core(MockCore),
 }
 
+/// The registers of a mocked core. ARM registers are keyed by their memory mapped address,
+/// RISC-V registers by their number in abstract commands.
+///
+/// Clones share the register values and the halt state of the core, so the registers can be
+/// inspected and changed while the probe is used by a session, for instance to emulate a hit
+/// watchpoint.
+#[derive(Debug, Clone, Default)]
+pub struct MockedRegisters {
+    values: Arc<Mutex<HashMap<u64, u32>>>,
+    halted: Arc<AtomicBool>,
+}
+
+impl MockedRegisters {
+    /// Returns the value of the register at `address`, or zero if it was never written.
+    pub fn read(&self, address: u64) -> u32 {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&address)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Sets the value of the register at `address`, bypassing the emulated register behavior.
+    pub fn write(&self, address: u64, value: u32) {
+        self.values.lock().unwrap().insert(address, value);
+    }
+
+    /// Halts the mocked core, like the core does itself when it hits a breakpoint or watchpoint.
+    ///
+    /// The reason of the halt is taken from the DFSR register of an ARM core, or the `dcsr`
+    /// register of a RISC-V hart.
+    pub fn halt(&self) {
+        self.halted.store(true, Ordering::SeqCst);
+    }
+
+    fn is_halted(&self) -> bool {
+        self.halted.load(Ordering::SeqCst)
+    }
+
+    fn set_halted(&self, halted: bool) {
+        self.halted.store(halted, Ordering::SeqCst);
+    }
+}
+
 struct MockCore {
     dhcsr: Dhcsr,
 
-    /// Is the core halted?
-    halted: bool,
+    /// Values of the other memory mapped registers, registers which were not written read as
+    /// zero, and whether the core is halted
+    registers: MockedRegisters,
 }
 
 impl MockCore {
+    /// Address of the DFSR register, whose bits are cleared by writing ones
+    const DFSR: u64 = 0xE000_ED30;
+    /// Address of the FP_CTRL register of the breakpoint unit
+    const FP_CTRL: u64 = 0xE000_2000;
+    /// The read-only NUM_CODE fields of FP_CTRL
+    const FP_CTRL_NUM_CODE: u32 = 0x70f0;
+
     pub fn new() -> Self {
         Self {
             dhcsr: Dhcsr(0),
-            halted: false,
+            registers: MockedRegisters::default(),
         }
     }
 }
@@ -89,7 +158,7 @@ impl ArmProbe for &mut MockCore {
                 Dhcsr::ADDRESS_OFFSET => {
                     let mut dhcsr: u32 = self.dhcsr.into();
 
-                    if self.halted {
+                    if self.registers.is_halted() {
                         dhcsr |= 1 << 17;
                     }
 
@@ -102,8 +171,8 @@ impl ArmProbe for &mut MockCore {
                 }
 
                 _ => {
-                    *val = 0;
-                    println!("Read {:#010x} = 0", address);
+                    *val = self.registers.read(address);
+                    println!("Read {:#010x} = {:#010x}", address, val);
                 }
             }
         }
@@ -133,12 +202,28 @@ impl ArmProbe for &mut MockCore {
                         self.dhcsr = Dhcsr::from(*word & 0xffff);
                         println!("Write DHCSR = {:#010x}", word);
 
-                        let new_halted_state = self.dhcsr.c_halt();
+                        // A step halts the core again after a single instruction.
+                        let new_halted_state = self.dhcsr.c_halt() || self.dhcsr.c_step();
 
-                        self.halted = new_halted_state;
+                        self.registers.set_halted(new_halted_state);
                     }
                 }
-                _ => println!("Write {:#010x} = {:#010x}", address, word),
+                MockCore::DFSR => {
+                    let previous = self.registers.read(address);
+                    self.registers.write(address, previous & !*word);
+                    println!("Write DFSR = {:#010x}", word);
+                }
+                MockCore::FP_CTRL => {
+                    let previous = self.registers.read(address);
+                    let value = (previous & MockCore::FP_CTRL_NUM_CODE)
+                        | (*word & !MockCore::FP_CTRL_NUM_CODE);
+                    self.registers.write(address, value);
+                    println!("Write FP_CTRL = {:#010x}", word);
+                }
+                _ => {
+                    self.registers.write(address, *word);
+                    println!("Write {:#010x} = {:#010x}", address, word);
+                }
             }
         }
 
@@ -150,7 +235,7 @@ impl ArmProbe for &mut MockCore {
     }
 
     fn flush(&mut self) -> Result<(), ArmError> {
-        todo!()
+        Ok(())
     }
 
     fn supports_native_64bit_access(&mut self) -> bool {
@@ -177,6 +262,221 @@ impl ArmProbe for &mut MockCore {
     fn update_core_status(&mut self, _state: crate::CoreStatus) {}
 }
 
+/// A RISC-V hart with a 0.13 debug module, which is accessed through the `dmi` register of the
+/// debug transport module.
+///
+/// Registers are only accessed with abstract commands, there is no program buffer or system
+/// bus access.
+struct MockRiscvCore {
+    /// The `tdata1` and `tdata2` registers of each trigger
+    triggers: Vec<(u32, u32)>,
+    /// The `maskmax` field of `tdata1`, the largest NAPOT region is 2^maskmax bytes
+    maskmax: u32,
+    tselect: u32,
+
+    dmcontrol: u32,
+    data0: u32,
+    cmderr: u32,
+    resumeack: bool,
+
+    /// The data read by the previous `dmi` access, which is shifted out by the next access
+    dmi_result: u32,
+
+    /// Values of the other registers, registers which were not written read as zero, and
+    /// whether the hart is halted
+    registers: MockedRegisters,
+}
+
+impl MockRiscvCore {
+    /// Address of the `dtmcs` JTAG register
+    const DTMCS: u32 = 0x10;
+    /// Address of the `dmi` JTAG register
+    const DMI: u32 = 0x11;
+    /// Version 0.13 of the debug transport module, with 7 address bits in `dmi`
+    const DTMCS_VALUE: u32 = 7 << 4 | 1;
+
+    const TSELECT: u32 = 0x7a0;
+    const TDATA1: u32 = 0x7a1;
+    const TDATA2: u32 = 0x7a2;
+    const TINFO: u32 = 0x7a4;
+    const DCSR: u32 = 0x7b0;
+    /// The read-only `type` and `maskmax` fields of `tdata1`
+    const TDATA1_READ_ONLY: u32 = 0xF7E0_0000;
+
+    /// Creates a halted RV32I hart with `triggers` address match triggers.
+    fn new(triggers: usize, maskmax: u32) -> Self {
+        let registers = MockedRegisters::default();
+        // RV32I
+        registers.write(0x301, 0x4000_0100);
+        registers.halt();
+
+        Self {
+            triggers: vec![(Self::tdata1(0, maskmax), 0); triggers],
+            maskmax,
+            tselect: 0,
+            dmcontrol: 0,
+            data0: 0,
+            cmderr: 0,
+            resumeack: false,
+            dmi_result: 0,
+            registers,
+        }
+    }
+
+    /// Returns `value` written to `tdata1`, with the read-only fields of an `mcontrol` trigger.
+    fn tdata1(value: u32, maskmax: u32) -> u32 {
+        (value & !Self::TDATA1_READ_ONLY) | 2 << 28 | maskmax << 21
+    }
+
+    /// Sets the cause field of `dcsr`.
+    fn set_halt_cause(&self, cause: u32) {
+        let dcsr = self.registers.read(Self::DCSR as u64);
+        self.registers
+            .write(Self::DCSR as u64, dcsr & !(0x7 << 6) | cause << 6);
+    }
+
+    fn read_dm_register(&mut self, address: u32) -> u32 {
+        match address {
+            // data0
+            0x04 => self.data0,
+            // dmcontrol
+            0x10 => self.dmcontrol,
+            // dmstatus, version 0.13 and authenticated
+            0x11 => {
+                let mut dmstatus = 1 << 7 | 2;
+                if self.registers.is_halted() {
+                    dmstatus |= 0x3 << 8;
+                } else {
+                    dmstatus |= 0x3 << 10;
+                }
+                if self.resumeack {
+                    dmstatus |= 0x3 << 16;
+                }
+                dmstatus
+            }
+            // abstractcs, with a single data register
+            0x16 => self.cmderr << 8 | 1,
+            _ => 0,
+        }
+    }
+
+    fn write_dm_register(&mut self, address: u32, value: u32) {
+        match address {
+            0x04 => self.data0 = value,
+            0x10 => {
+                // haltreq
+                if value & 1 << 31 != 0 {
+                    self.registers.halt();
+                    self.set_halt_cause(3);
+                    self.resumeack = false;
+                }
+                // resumereq, a step halts the hart again after a single instruction.
+                if value & 1 << 30 != 0 {
+                    self.resumeack = true;
+                    if self.registers.read(Self::DCSR as u64) & 1 << 2 != 0 {
+                        self.set_halt_cause(4);
+                    } else {
+                        self.registers.set_halted(false);
+                    }
+                }
+                self.dmcontrol = value & !(1 << 31 | 1 << 30 | 1 << 28);
+            }
+            // The cmderr field of abstractcs is cleared by writing ones
+            0x16 => self.cmderr &= !(value >> 8 & 0x7),
+            0x17 => self.execute_command(value),
+            _ => {}
+        }
+    }
+
+    /// Executes an abstract command, only register accesses are supported.
+    fn execute_command(&mut self, command: u32) {
+        if self.cmderr != 0 {
+            return;
+        }
+
+        let cmd_type = command >> 24;
+        let aarsize = command >> 20 & 0x7;
+        let postexec = command & 1 << 18 != 0;
+        if cmd_type != 0 || aarsize != 2 || postexec {
+            // Not supported
+            self.cmderr = 2;
+            return;
+        }
+
+        if !self.registers.is_halted() {
+            // Halt/resume
+            self.cmderr = 4;
+            return;
+        }
+
+        let transfer = command & 1 << 17 != 0;
+        let write = command & 1 << 16 != 0;
+        let regno = command & 0xffff;
+
+        if transfer && write {
+            self.write_register(regno, self.data0);
+        } else if transfer {
+            self.data0 = self.read_register(regno);
+        }
+    }
+
+    fn read_register(&self, regno: u32) -> u32 {
+        let trigger = self.triggers.get(self.tselect as usize);
+        match regno {
+            Self::TSELECT => self.tselect,
+            Self::TDATA1 => trigger.map_or(0, |trigger| trigger.0),
+            Self::TDATA2 => trigger.map_or(0, |trigger| trigger.1),
+            // Only mcontrol triggers exist, tinfo is 1 for triggers which don't exist.
+            Self::TINFO => trigger.map_or(1, |_| 1 << 2),
+            _ => self.registers.read(regno as u64),
+        }
+    }
+
+    fn write_register(&mut self, regno: u32, value: u32) {
+        let maskmax = self.maskmax;
+        match (regno, self.triggers.get_mut(self.tselect as usize)) {
+            // Selecting a trigger which doesn't exist keeps the previous selection.
+            (Self::TSELECT, _) => {
+                if (value as usize) < self.triggers.len() {
+                    self.tselect = value;
+                }
+            }
+            (Self::TDATA1, Some(trigger)) => trigger.0 = Self::tdata1(value, maskmax),
+            (Self::TDATA2, Some(trigger)) => trigger.1 = value,
+            (Self::TDATA1 | Self::TDATA2 | Self::TINFO, None) => {}
+            _ => self.registers.write(regno as u64, value),
+        }
+    }
+
+    /// Shifts `data` through the JTAG data register at `address`, returning the captured bits.
+    fn scan(&mut self, address: u32, data: &[u8]) -> Vec<u8> {
+        match address {
+            Self::DTMCS => Self::DTMCS_VALUE.to_le_bytes().to_vec(),
+            Self::DMI => {
+                let mut bytes = [0; 16];
+                bytes[..data.len()].copy_from_slice(data);
+                let request = u128::from_le_bytes(bytes);
+
+                let op = request & 0x3;
+                let value = (request >> 2) as u32;
+                let dmi_address = (request >> 34) as u32;
+
+                // The captured value is the result of the previous access, with a successful op.
+                let response = (self.dmi_result as u128) << 2;
+
+                match op {
+                    1 => self.dmi_result = self.read_dm_register(dmi_address),
+                    2 => self.write_dm_register(dmi_address, value),
+                    _ => {}
+                }
+
+                response.to_le_bytes()[..data.len()].to_vec()
+            }
+            _ => vec![0; data.len()],
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Operation {
     ReadRawApRegister {
@@ -209,6 +509,10 @@ impl FakeProbe {
             operations: RefCell::new(VecDeque::new()),
 
             memory_ap: MockedAp::MemoryAp(MockMemoryAp::with_pattern()),
+
+            debug_base_address: None,
+
+            riscv_core: None,
         }
     }
 
@@ -225,9 +529,58 @@ impl FakeProbe {
             operations: RefCell::new(VecDeque::new()),
 
             memory_ap: MockedAp::Core(MockCore::new()),
+
+            debug_base_address: None,
+
+            riscv_core: None,
+        }
+    }
+
+    /// Fake probe with a mocked core, whose memory mapped registers start with the values in
+    /// `registers`, keyed by address.
+    pub fn with_mocked_core_registers(registers: impl IntoIterator<Item = (u64, u32)>) -> Self {
+        let core = MockCore::new();
+        for (address, value) in registers {
+            core.registers.write(address, value);
+        }
+
+        FakeProbe {
+            memory_ap: MockedAp::Core(core),
+            ..FakeProbe::with_mocked_core()
+        }
+    }
+
+    /// Fake probe with a mocked RISC-V hart, connected over JTAG. The hart has `triggers`
+    /// address match triggers, which match regions of up to 2^`maskmax` bytes.
+    pub fn with_mocked_riscv_core(triggers: usize, maskmax: u32) -> Self {
+        FakeProbe {
+            protocol: WireProtocol::Jtag,
+            riscv_core: Some(MockRiscvCore::new(triggers, maskmax)),
+            ..FakeProbe::new()
         }
     }
 
+    /// Returns the registers of the mocked core, which can be changed while the probe is in
+    /// use, or `None` if the probe does not mock a core.
+    pub fn mocked_core_registers(&self) -> Option<MockedRegisters> {
+        if let Some(core) = &self.riscv_core {
+            return Some(core.registers.clone());
+        }
+
+        match &self.memory_ap {
+            MockedAp::Core(core) => Some(core.registers.clone()),
+            MockedAp::MemoryAp(_) => None,
+        }
+    }
+
+    /// Reports a CoreSight component at `address` behind the memory AP, whose registers
+    /// are read through the mocked memory.
+    ///
+    /// Without it, reading the information of the AP fails, so no components are found.
+    pub fn set_debug_base_address(&mut self, address: u64) {
+        self.debug_base_address = Some(address);
+    }
+
     /// This sets the read handler for DAP register reads.
     /// Can be used to hook into the read.
     pub fn set_dap_register_read_handler(
@@ -365,6 +718,51 @@ impl DebugProbe for FakeProbe {
     fn has_arm_interface(&self) -> bool {
         true
     }
+
+    fn try_get_riscv_interface(
+        self: Box<Self>,
+    ) -> Result<RiscvCommunicationInterface, (Box<dyn DebugProbe>, RiscvError)> {
+        if self.riscv_core.is_none() {
+            return Err((
+                DebugProbe::into_probe(self),
+                DebugProbeError::InterfaceNotAvailable("RISCV").into(),
+            ));
+        }
+
+        RiscvCommunicationInterface::new(self).map_err(|(probe, err)| (probe.into_probe(), err))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.riscv_core.is_some()
+    }
+}
+
+impl JTAGAccess for FakeProbe {
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        self.write_register(address, &vec![0; (len as usize + 7) / 8], len)
+    }
+
+    fn set_idle_cycles(&mut self, _idle_cycles: u8) {}
+
+    fn get_idle_cycles(&self) -> u8 {
+        0
+    }
+
+    fn set_ir_len(&mut self, _len: u32) {}
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        _len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        let core = self
+            .riscv_core
+            .as_mut()
+            .ok_or(DebugProbeError::InterfaceNotAvailable("JTAG"))?;
+
+        Ok(core.scan(address, data))
+    }
 }
 
 impl RawDapAccess for FakeProbe {
@@ -419,6 +817,9 @@ impl RawDapAccess for FakeProbe {
 struct FakeArmInterface<S: ArmDebugState> {
     probe: Box<FakeProbe>,
 
+    /// The information of the last AP read with `ap_information`
+    ap_information: Option<ApInformation>,
+
     _state: S,
 }
 
@@ -430,6 +831,7 @@ impl FakeArmInterface<Uninitialized> {
 
         Self {
             probe,
+            ap_information: None,
             _state: state,
         }
     }
@@ -451,6 +853,7 @@ impl FakeArmInterface<Initialized> {
     ) -> Self {
         FakeArmInterface::<Initialized> {
             probe: interface.probe,
+            ap_information: None,
             _state: Initialized::new(sequence, false),
         }
     }
@@ -523,9 +926,25 @@ impl ArmProbeInterface for FakeArmInterface<Initialized> {
 
     fn ap_information(
         &mut self,
-        _access_port: crate::architecture::arm::ap::GenericAp,
+        access_port: crate::architecture::arm::ap::GenericAp,
     ) -> Result<&crate::architecture::arm::ApInformation, ArmError> {
-        todo!()
+        let Some(debug_base_address) = self.probe.debug_base_address else {
+            return Err(DebugProbeError::NotImplemented("ap_information").into());
+        };
+
+        let ap_information =
+            self.ap_information
+                .insert(ApInformation::MemoryAp(MemoryApInformation {
+                    address: access_port.ap_address(),
+                    supports_only_32bit_data_size: false,
+                    debug_base_address,
+                    supports_hnonsec: false,
+                    has_large_data_extension: false,
+                    has_large_address_extension: false,
+                    device_enabled: true,
+                }));
+
+        Ok(ap_information)
     }
 
     fn num_access_ports(&mut self, _dp: DpAddress) -> Result<usize, ArmError> {
@@ -1,21 +1,30 @@
 use crate::architecture::arm::component::get_arm_components;
 use crate::architecture::arm::sequences::{ArmDebugSequence, DefaultArmSequence};
-use crate::architecture::arm::{ArmError, DpAddress};
+use crate::architecture::arm::{valid_32bit_arm_address, ArmError, DpAddress};
 use crate::architecture::riscv::communication_interface::RiscvError;
 use crate::config::{ChipInfo, CoreExt, RegistryError, Target, TargetSelector};
 use crate::core::{Architecture, CombinedCoreState};
+use crate::debug::DebugInfo;
 use crate::probe::fake_probe::FakeProbe;
+use crate::watchpoint::{WatchpointHandle, WatchpointRequest, WatchpointUnit};
 use crate::{
     architecture::{
         arm::{
-            communication_interface::ArmProbeInterface, component::TraceSink,
-            memory::CoresightComponent, SwoReader,
+            ap::AccessPort,
+            communication_interface::ArmProbeInterface,
+            component::{
+                find_component, AllocationStrategy, CyccntReservation, CyccntReservations, Dwt,
+                TraceSink, WatchKind, WatchpointRef,
+            },
+            memory::{CoresightComponent, PeripheralType},
+            SwoReader,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
     config::DebugSequence,
 };
 use crate::{AttachMethod, Core, CoreType, Error, Lister, Probe};
+use std::collections::HashMap;
 use std::ops::DerefMut;
 use std::{fmt, sync::Arc, time::Duration};
 
@@ -43,6 +52,17 @@ pub struct Session {
     interface: ArchitectureInterface,
     cores: Vec<CombinedCoreState>,
     configured_trace_sink: Option<TraceSink>,
+    /// The DWT unit used by each active data watchpoint, keyed by core index, watched address,
+    /// length and kind of access.
+    data_watchpoints: HashMap<(usize, u32, u32, WatchKind), usize>,
+    /// The DWT unit used by each active instruction watchpoint, keyed by core index and address.
+    instruction_watchpoints: HashMap<(usize, u32), usize>,
+    /// Whether cores are halted while their DWT is configured for a watchpoint.
+    halt_for_watchpoint_setup: bool,
+    /// The order in which DWT units are allocated for watchpoints.
+    watchpoint_allocation: AllocationStrategy,
+    /// The DWT cycle counters reserved in this session.
+    cyccnt_reservations: CyccntReservations,
 }
 
 pub(crate) enum ArchitectureInterface {
@@ -220,6 +240,11 @@ impl Session {
                 interface: ArchitectureInterface::Arm(interface),
                 cores,
                 configured_trace_sink: None,
+                data_watchpoints: HashMap::new(),
+                instruction_watchpoints: HashMap::new(),
+                halt_for_watchpoint_setup: true,
+                watchpoint_allocation: AllocationStrategy::default(),
+                cyccnt_reservations: CyccntReservations::default(),
             };
 
             {
@@ -243,6 +268,11 @@ impl Session {
                 interface: ArchitectureInterface::Arm(interface),
                 cores,
                 configured_trace_sink: None,
+                data_watchpoints: HashMap::new(),
+                instruction_watchpoints: HashMap::new(),
+                halt_for_watchpoint_setup: true,
+                watchpoint_allocation: AllocationStrategy::default(),
+                cyccnt_reservations: CyccntReservations::default(),
             })
         }
     }
@@ -278,6 +308,11 @@ impl Session {
             interface: ArchitectureInterface::Riscv(Box::new(interface)),
             cores,
             configured_trace_sink: None,
+            data_watchpoints: HashMap::new(),
+            instruction_watchpoints: HashMap::new(),
+            halt_for_watchpoint_setup: true,
+            watchpoint_allocation: AllocationStrategy::default(),
+            cyccnt_reservations: CyccntReservations::default(),
         };
 
         {
@@ -563,8 +598,21 @@ impl Session {
     }
 
     /// Begin tracing a memory address over SWV.
+    ///
+    /// The DWT behind the default debug port is used, see [`Session::add_swv_data_trace_on_dp`]
+    /// for targets with multiple debug ports.
     pub fn add_swv_data_trace(&mut self, unit: usize, address: u32) -> Result<(), ArmError> {
-        let components = self.get_arm_components(DpAddress::Default)?;
+        self.add_swv_data_trace_on_dp(DpAddress::Default, unit, address)
+    }
+
+    /// Begin tracing a memory address over SWV, using the DWT behind the debug port `dp`.
+    pub fn add_swv_data_trace_on_dp(
+        &mut self,
+        dp: DpAddress,
+        unit: usize,
+        address: u32,
+    ) -> Result<(), ArmError> {
+        let components = self.get_arm_components(dp)?;
         let interface = self.get_arm_interface()?;
         crate::architecture::arm::component::add_swv_data_trace(
             interface,
@@ -576,11 +624,455 @@ impl Session {
 
     /// Stop tracing from a given SWV unit
     pub fn remove_swv_data_trace(&mut self, unit: usize) -> Result<(), ArmError> {
-        let components = self.get_arm_components(DpAddress::Default)?;
+        self.remove_swv_data_trace_on_dp(DpAddress::Default, unit)
+    }
+
+    /// Stop tracing from a given SWV unit of the DWT behind the debug port `dp`.
+    pub fn remove_swv_data_trace_on_dp(
+        &mut self,
+        dp: DpAddress,
+        unit: usize,
+    ) -> Result<(), ArmError> {
+        let components = self.get_arm_components(dp)?;
         let interface = self.get_arm_interface()?;
         crate::architecture::arm::component::remove_swv_data_trace(interface, &components, unit)
     }
 
+    /// Runs `f` while core `core_index` is halted.
+    ///
+    /// If the core is running, it is halted before calling `f` and resumed afterwards, also if
+    /// `f` fails. The result of `f` is returned even if the core can't be resumed, in which
+    /// case a warning is logged.
+    pub fn with_core_halted<T>(
+        &mut self,
+        core_index: usize,
+        f: impl FnOnce(&mut Session) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let was_running = {
+            let mut core = self.core(core_index)?;
+            if core.core_halted()? {
+                false
+            } else {
+                core.halt(Duration::from_millis(100))?;
+                true
+            }
+        };
+
+        let result = f(self);
+
+        if was_running {
+            if let Err(e) = self.core(core_index).and_then(|mut core| core.run()) {
+                tracing::warn!("Failed to resume core {core_index}: {e}");
+            }
+        }
+
+        result
+    }
+
+    /// Sets whether cores are halted while their DWT is configured for a watchpoint.
+    ///
+    /// Configuring a comparator while the core is running can cause spurious matches, so this is
+    /// enabled by default. Targets which support programming the DWT at runtime can disable it,
+    /// to keep the core running when watchpoints are added.
+    pub fn set_halt_for_watchpoint_setup(&mut self, halt: bool) {
+        self.halt_for_watchpoint_setup = halt;
+    }
+
+    /// Sets the order in which DWT units are allocated for data and instruction watchpoints.
+    ///
+    /// [`AllocationStrategy::HighestFirst`] keeps the low units free for firmware which uses
+    /// specific comparators itself. The default is [`AllocationStrategy::LowestFirst`].
+    pub fn set_watchpoint_allocation_strategy(&mut self, strategy: AllocationStrategy) {
+        self.watchpoint_allocation = strategy;
+    }
+
+    /// Runs `f`, which configures the DWT of core `core_index`, halting the core if configured
+    /// by [`Session::set_halt_for_watchpoint_setup`].
+    fn configure_dwt<T>(
+        &mut self,
+        core_index: usize,
+        f: impl FnOnce(&mut Session) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if self.halt_for_watchpoint_setup {
+            self.with_core_halted(core_index, f)
+        } else {
+            f(self)
+        }
+    }
+
+    /// Set a data watchpoint described by `request`, on any architecture.
+    ///
+    /// On ARM, a DWT unit is allocated with [`Session::add_data_watchpoint`]. On RISC-V, a
+    /// trigger unit is set with [`Core::set_hw_watchpoint`]. The returned handle removes the
+    /// watchpoint with [`Session::remove_watchpoint`].
+    pub fn add_watchpoint(
+        &mut self,
+        request: WatchpointRequest,
+    ) -> Result<WatchpointHandle, Error> {
+        let WatchpointRequest {
+            core_index,
+            address,
+            length,
+            kind,
+        } = request;
+
+        let unit = match self.architecture() {
+            Architecture::Arm => {
+                let address = valid_32bit_arm_address(address)?;
+                WatchpointUnit::Dwt(self.add_data_watchpoint(core_index, address, length, kind)?)
+            }
+            Architecture::Riscv => {
+                self.core(core_index)?
+                    .set_hw_watchpoint(address, length, kind)?;
+                WatchpointUnit::Trigger
+            }
+        };
+
+        Ok(WatchpointHandle {
+            core_index,
+            address,
+            unit,
+        })
+    }
+
+    /// Remove a watchpoint previously set with [`Session::add_watchpoint`].
+    pub fn remove_watchpoint(&mut self, handle: WatchpointHandle) -> Result<(), Error> {
+        match handle.unit {
+            WatchpointUnit::Dwt(unit) => {
+                self.remove_data_watchpoint_by_unit(handle.core_index, unit)
+            }
+            WatchpointUnit::Trigger => self
+                .core(handle.core_index)?
+                .clear_hw_watchpoint(handle.address),
+        }
+    }
+
+    /// Set a data watchpoint on `length` bytes starting at `address` for core `core_index`.
+    ///
+    /// Each core has its own DWT, so the watchpoint only triggers on accesses by this core. The
+    /// DWT is accessed through the debug port and access port of the core.
+    /// A free DWT unit is allocated for the watchpoint, and its index is returned. An existing
+    /// watchpoint of the same kind and length on the same address is replaced, while watchpoints
+    /// of different kinds or lengths on one address use separate units. If all units are in use,
+    /// [`Error::NoFreeWatchpointUnits`] is returned.
+    ///
+    /// The core is halted while the DWT is configured, unless this is disabled with
+    /// [`Session::set_halt_for_watchpoint_setup`].
+    pub fn add_data_watchpoint(
+        &mut self,
+        core_index: usize,
+        address: u32,
+        length: u32,
+        kind: WatchKind,
+    ) -> Result<usize, Error> {
+        self.configure_dwt(core_index, |session| {
+            if let Some(&unit) = session
+                .data_watchpoints
+                .get(&(core_index, address, length, kind))
+            {
+                session.remove_data_watchpoint_by_unit(core_index, unit)?;
+            }
+
+            let strategy = session.watchpoint_allocation;
+            let components = session.core_arm_components(core_index)?;
+            let interface = session.get_arm_interface()?;
+
+            if crate::architecture::arm::component::available_watchpoints(interface, &components)?
+                == 0
+            {
+                return Err(Error::NoFreeWatchpointUnits);
+            }
+
+            let unit = crate::architecture::arm::component::add_watchpoint(
+                interface,
+                &components,
+                address,
+                length,
+                kind,
+                strategy,
+            )?;
+
+            session
+                .data_watchpoints
+                .insert((core_index, address, length, kind), unit);
+
+            Ok(unit)
+        })
+    }
+
+    /// Set a data watchpoint on the variable `name` for core `core_index`.
+    ///
+    /// The address and size of the variable are taken from the symbol table of `debug_info`,
+    /// and the watchpoint is set with [`Session::add_data_watchpoint`]. The size therefore has
+    /// to be a power of two, with the variable aligned to it.
+    pub fn add_data_watchpoint_symbol(
+        &mut self,
+        core_index: usize,
+        debug_info: &DebugInfo,
+        name: &str,
+        kind: WatchKind,
+    ) -> Result<usize, Error> {
+        let (address, size) = debug_info
+            .symbol_region(name)
+            .ok_or_else(|| anyhow::anyhow!("The symbol {name} was not found"))?;
+
+        if size == 0 {
+            return Err(anyhow::anyhow!("The size of the symbol {name} is unknown").into());
+        }
+
+        let address = valid_32bit_arm_address(address)?;
+        let length = u32::try_from(size).map_err(|_| {
+            anyhow::anyhow!("The symbol {name} of {size} bytes is too large to watch")
+        })?;
+
+        self.add_data_watchpoint(core_index, address, length, kind)
+    }
+
+    /// Returns the number of DWT units of core `core_index`, or 0 if the core has no DWT.
+    pub fn data_watchpoint_units(&mut self, core_index: usize) -> Result<usize, Error> {
+        let components = self.core_arm_components(core_index)?;
+        let interface = self.get_arm_interface()?;
+        Ok(crate::architecture::arm::component::watchpoint_units(
+            interface,
+            &components,
+        )?)
+    }
+
+    /// Returns the number of DWT units of core `core_index` which are still available for data watchpoints.
+    pub fn available_data_watchpoints(&mut self, core_index: usize) -> Result<usize, Error> {
+        let components = self.core_arm_components(core_index)?;
+        let interface = self.get_arm_interface()?;
+        Ok(crate::architecture::arm::component::available_watchpoints(
+            interface,
+            &components,
+        )?)
+    }
+
+    /// Remove the data watchpoints of kind `kind` previously set on `address` for core `core_index`.
+    ///
+    /// Watchpoints of any length are removed, use [`Session::remove_data_watchpoint_by_unit`] to
+    /// remove a single one of them.
+    pub fn remove_data_watchpoint(
+        &mut self,
+        core_index: usize,
+        address: u32,
+        kind: WatchKind,
+    ) -> Result<(), Error> {
+        let units: Vec<usize> = self
+            .data_watchpoints
+            .iter()
+            .filter(|(&(core, watched, _, watched_kind), _)| {
+                core == core_index && watched == address && watched_kind == kind
+            })
+            .map(|(_, &unit)| unit)
+            .collect();
+
+        if units.is_empty() {
+            return Err(ArmError::Other(anyhow::anyhow!(
+                "No {kind} watchpoint is set on address {address:#010x}"
+            ))
+            .into());
+        }
+
+        for unit in units {
+            self.remove_data_watchpoint_by_unit(core_index, unit)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the data or instruction watchpoint which uses DWT unit `unit` of core `core_index`.
+    ///
+    /// `unit` is the index returned by [`Session::add_data_watchpoint`]. The comparator is
+    /// disabled even if the watchpoint was not set through this session.
+    pub fn remove_data_watchpoint_by_unit(
+        &mut self,
+        core_index: usize,
+        unit: usize,
+    ) -> Result<(), Error> {
+        let components = self.core_arm_components(core_index)?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::remove_watchpoint(
+            interface,
+            &components,
+            WatchpointRef::Unit(unit),
+        )?;
+
+        self.data_watchpoints
+            .retain(|&(core, _, _, _), &mut used| core != core_index || used != unit);
+        self.instruction_watchpoints
+            .retain(|&(core, _), &mut used| core != core_index || used != unit);
+
+        Ok(())
+    }
+
+    /// Disable all comparators and counters of the DWT of core `core_index`, and enable it again.
+    ///
+    /// This recovers from a bad data trace or watchpoint configuration. All data and instruction
+    /// watchpoints of the core are removed. A cycle counter reserved with
+    /// [`Session::reserve_cyccnt`] keeps running.
+    pub fn reset_dwt(&mut self, core_index: usize) -> Result<(), Error> {
+        let components = self.core_arm_components(core_index)?;
+        let reservations = self.cyccnt_reservations.clone();
+        let interface = self.get_arm_interface()?;
+        let mut dwt = Dwt::new(interface, find_component(&components, PeripheralType::Dwt)?);
+        dwt.set_cyccnt_reservations(reservations);
+        dwt.disable_all()?;
+        dwt.enable()?;
+
+        self.data_watchpoints
+            .retain(|&(core, _, _, _), _| core != core_index);
+        self.instruction_watchpoints
+            .retain(|&(core, _), _| core != core_index);
+
+        Ok(())
+    }
+
+    /// Reserves the DWT cycle counter of core `core_index`, see [`Dwt::reserve_cyccnt`].
+    ///
+    /// The reservation is respected by [`Session::reset_dwt`], and by all [`Dwt`]s given the
+    /// reservations of [`Session::cyccnt_reservations`].
+    pub fn reserve_cyccnt(&mut self, core_index: usize) -> Result<CyccntReservation, Error> {
+        let components = self.core_arm_components(core_index)?;
+        let reservations = self.cyccnt_reservations.clone();
+        let interface = self.get_arm_interface()?;
+        let mut dwt = Dwt::new(interface, find_component(&components, PeripheralType::Dwt)?);
+        dwt.set_cyccnt_reservations(reservations);
+        Ok(dwt.reserve_cyccnt()?)
+    }
+
+    /// Returns the DWT cycle counter reservations of this session, to share them with a
+    /// [`Dwt`] through [`Dwt::set_cyccnt_reservations`].
+    pub fn cyccnt_reservations(&self) -> CyccntReservations {
+        self.cyccnt_reservations.clone()
+    }
+
+    /// Remove all data and instruction watchpoints set through this session, on all cores.
+    ///
+    /// Only the DWT units allocated by this session are disabled, comparators configured by other
+    /// means, like data trace, are kept. The removal is attempted for every watchpoint, and all
+    /// of them are forgotten even if some can't be removed, in which case the first error is
+    /// returned.
+    pub fn clear_all_watchpoints(&mut self) -> Result<(), Error> {
+        let units: Vec<(usize, usize)> = self
+            .data_watchpoints
+            .drain()
+            .map(|((core_index, _, _, _), unit)| (core_index, unit))
+            .chain(
+                self.instruction_watchpoints
+                    .drain()
+                    .map(|((core_index, _), unit)| (core_index, unit)),
+            )
+            .collect();
+
+        let mut result = Ok(());
+        for (core_index, unit) in units {
+            let removed = self.core_arm_components(core_index).and_then(|components| {
+                let interface = self.get_arm_interface()?;
+                Ok(crate::architecture::arm::component::remove_watchpoint(
+                    interface,
+                    &components,
+                    WatchpointRef::Unit(unit),
+                )?)
+            });
+
+            if let Err(e) = removed {
+                tracing::warn!("Failed to remove watchpoint unit {unit} of core {core_index}: {e}");
+                result = result.and(Err(e));
+            }
+        }
+
+        result
+    }
+
+    /// Set an instruction watchpoint on `address` for core `core_index`.
+    ///
+    /// The watchpoint uses a DWT unit to match instruction fetches from `address`, which provides
+    /// breakpoints in addition to the ones of [`Core::set_hw_breakpoint`]. In contrast to those,
+    /// the core may only halt after the instruction at `address` has executed, and the units are
+    /// shared with data watchpoints. The index of the allocated unit is returned.
+    pub fn add_instruction_watchpoint(
+        &mut self,
+        core_index: usize,
+        address: u32,
+    ) -> Result<usize, Error> {
+        self.configure_dwt(core_index, |session| {
+            if session
+                .instruction_watchpoints
+                .contains_key(&(core_index, address))
+            {
+                session.remove_instruction_watchpoint(core_index, address)?;
+            }
+
+            let strategy = session.watchpoint_allocation;
+            let components = session.core_arm_components(core_index)?;
+            let interface = session.get_arm_interface()?;
+
+            if crate::architecture::arm::component::available_watchpoints(interface, &components)?
+                == 0
+            {
+                return Err(Error::NoFreeWatchpointUnits);
+            }
+
+            let unit = crate::architecture::arm::component::add_instruction_watchpoint(
+                interface,
+                &components,
+                address,
+                strategy,
+            )?;
+
+            session
+                .instruction_watchpoints
+                .insert((core_index, address), unit);
+
+            Ok(unit)
+        })
+    }
+
+    /// Remove the instruction watchpoint previously set on `address` for core `core_index`.
+    pub fn remove_instruction_watchpoint(
+        &mut self,
+        core_index: usize,
+        address: u32,
+    ) -> Result<(), Error> {
+        let Some(&unit) = self.instruction_watchpoints.get(&(core_index, address)) else {
+            return Err(ArmError::Other(anyhow::anyhow!(
+                "No instruction watchpoint is set on address {address:#010x}"
+            ))
+            .into());
+        };
+
+        let components = self.core_arm_components(core_index)?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::remove_watchpoint(
+            interface,
+            &components,
+            WatchpointRef::Unit(unit),
+        )?;
+
+        self.instruction_watchpoints.remove(&(core_index, address));
+
+        Ok(())
+    }
+
+    /// Returns the ROM table components behind the memory AP of core `core_index`.
+    fn core_arm_components(&mut self, core_index: usize) -> Result<Vec<CoresightComponent>, Error> {
+        let memory_ap = self
+            .target
+            .cores
+            .get(core_index)
+            .ok_or(Error::CoreNotFound(core_index))?
+            .memory_ap()
+            .ok_or(ArmError::NoArmTarget)?;
+
+        let components = self.get_arm_components(memory_ap.ap_address().dp)?;
+
+        Ok(components
+            .into_iter()
+            .filter(|component| component.ap.ap_address() == memory_ap.ap_address())
+            .collect())
+    }
+
     /// Return the `Architecture` of the currently connected chip.
     pub fn architecture(&self) -> Architecture {
         match self.interface {
@@ -757,3 +1249,99 @@ impl Permissions {
 #[derive(Debug, Clone, thiserror::Error)]
 #[error("An operation could not be performed because it lacked the permission to do so: {0}")]
 pub struct MissingPermissions(pub String);
+
+#[cfg(test)]
+mod tests {
+    use crate::architecture::arm::component::{find_component, Dwt, WatchKind, COMPONENT_BASE};
+    use crate::architecture::arm::memory::PeripheralType;
+    use crate::probe::fake_probe::FakeProbe;
+    use crate::test::session_with_dwt;
+    use crate::{MemoryInterface, Permissions};
+
+    #[test]
+    fn all_watchpoints_are_forgotten() {
+        let mut session = FakeProbe::new()
+            .into_probe()
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap();
+
+        session.clear_all_watchpoints().unwrap();
+
+        session
+            .data_watchpoints
+            .insert((0, 0x2000_0000, 4, WatchKind::Write), 0);
+        session.instruction_watchpoints.insert((0, 0x0000_1000), 1);
+
+        // The fake probe has no DWT, so the units can't be disabled.
+        assert!(session.clear_all_watchpoints().is_err());
+        assert!(session.data_watchpoints.is_empty());
+        assert!(session.instruction_watchpoints.is_empty());
+    }
+
+    #[test]
+    fn watchpoints_differing_in_length_are_kept() {
+        let (mut session, _) = session_with_dwt(4);
+
+        let word = session
+            .add_data_watchpoint(0, 0x2000_0000, 4, WatchKind::Write)
+            .unwrap();
+        let block = session
+            .add_data_watchpoint(0, 0x2000_0000, 16, WatchKind::Write)
+            .unwrap();
+        assert_ne!(word, block);
+
+        session.remove_data_watchpoint_by_unit(0, word).unwrap();
+
+        assert_eq!(
+            session
+                .data_watchpoints
+                .get(&(0, 0x2000_0000, 16, WatchKind::Write)),
+            Some(&block)
+        );
+        // FUNCTION of the remaining unit still watches writes.
+        let function = session
+            .core(0)
+            .unwrap()
+            .read_word_32(COMPONENT_BASE + 0x28 + 16 * block as u64)
+            .unwrap();
+        assert_eq!(function & 0xf, 0b0110);
+    }
+
+    #[test]
+    fn instruction_watchpoint_is_removed_by_unit() {
+        let (mut session, _) = session_with_dwt(2);
+
+        let unit = session.add_instruction_watchpoint(0, 0x0000_1000).unwrap();
+        session.remove_data_watchpoint_by_unit(0, unit).unwrap();
+
+        assert!(session.instruction_watchpoints.is_empty());
+        assert!(session
+            .remove_instruction_watchpoint(0, 0x0000_1000)
+            .is_err());
+    }
+
+    #[test]
+    fn cycle_counter_is_reserved_for_the_session() {
+        let (mut session, registers) = session_with_dwt(2);
+        let ctrl = COMPONENT_BASE;
+        registers.write(ctrl, registers.read(ctrl) | 1);
+
+        let reservation = session.reserve_cyccnt(0).unwrap();
+        assert!(session.reserve_cyccnt(0).is_err());
+        session.reset_dwt(0).unwrap();
+        assert_eq!(registers.read(ctrl) & 1, 1);
+
+        let components = session.core_arm_components(0).unwrap();
+        let reservations = session.cyccnt_reservations();
+        let interface = session.get_arm_interface().unwrap();
+        let mut dwt = Dwt::new(
+            interface,
+            find_component(&components, PeripheralType::Dwt).unwrap(),
+        );
+        dwt.set_cyccnt_reservations(reservations);
+        assert!(dwt.reset_cycle_count().is_err());
+
+        reservation.release();
+        dwt.reset_cycle_count().unwrap();
+    }
+}
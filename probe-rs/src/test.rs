@@ -1,6 +1,59 @@
 //! Helpers for testing the crate
 
-use crate::MemoryInterface;
+use crate::architecture::arm::component::{dwt_registers, COMPONENT_BASE};
+use crate::probe::fake_probe::{FakeProbe, MockedRegisters};
+use crate::{MemoryInterface, Permissions, Session};
+
+/// Creates a session with a mocked nRF51 core, whose DWT implements `numcomp` comparators.
+///
+/// The registers of the core are returned as well, so they can be changed while the session
+/// is in use.
+pub(crate) fn session_with_dwt(numcomp: u8) -> (Session, MockedRegisters) {
+    let mut probe = FakeProbe::with_mocked_core_registers(dwt_registers(numcomp));
+    probe.set_debug_base_address(COMPONENT_BASE);
+    let registers = probe.mocked_core_registers().unwrap();
+
+    let session = probe
+        .into_probe()
+        .attach("nrf51822_xxAC", Permissions::default())
+        .unwrap();
+
+    (session, registers)
+}
+
+/// Returns the address of the COMP register of DWT comparator `unit` of [`session_with_dwt`].
+///
+/// The MASK and FUNCTION registers of the comparator follow it.
+pub(crate) fn dwt_comp_address(unit: u64) -> u64 {
+    COMPONENT_BASE + 0x20 + 16 * unit
+}
+
+/// Configures DWT comparator `unit` of [`session_with_dwt`] with the COMP, MASK and FUNCTION
+/// values `address`, `mask` and `function`.
+pub(crate) fn set_dwt_comparator(
+    registers: &MockedRegisters,
+    unit: u64,
+    address: u32,
+    mask: u32,
+    function: u32,
+) {
+    let comp = dwt_comp_address(unit);
+    registers.write(comp, address);
+    registers.write(comp + 4, mask);
+    registers.write(comp + 8, function);
+}
+
+/// Emulates a hit of DWT comparator `unit` of [`session_with_dwt`]: its MATCHED flag and
+/// DFSR.DWTTRAP are set, and the core halts.
+pub(crate) fn hit_dwt_comparator(registers: &MockedRegisters, unit: u64) {
+    let function = dwt_comp_address(unit) + 8;
+    registers.write(function, registers.read(function) | 1 << 24);
+    registers.write(DFSR, 1 << 2);
+    registers.halt();
+}
+
+/// Address of the DFSR of a Cortex-M core.
+const DFSR: u64 = 0xE000_ED30;
 
 #[derive(Debug)]
 pub(crate) struct MockMemory {
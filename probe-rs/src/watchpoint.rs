@@ -0,0 +1,51 @@
+//! Architecture independent data watchpoints, see [`Session::add_watchpoint`].
+
+use crate::architecture::arm::component::WatchKind;
+#[cfg(doc)]
+use crate::Session;
+
+/// A data watchpoint to be set with [`Session::add_watchpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointRequest {
+    /// The index of the core whose accesses are watched.
+    pub core_index: usize,
+    /// The start address of the watched region.
+    pub address: u64,
+    /// The length of the watched region in bytes.
+    ///
+    /// The DWT of ARM cores requires a power of two, and an address aligned to it. RISC-V
+    /// trigger units only watch the access to `address`, so lengths above one are rejected.
+    pub length: u32,
+    /// The kind of access which triggers the watchpoint.
+    pub kind: WatchKind,
+}
+
+/// Identifies a watchpoint set with [`Session::add_watchpoint`], for removing it with
+/// [`Session::remove_watchpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchpointHandle {
+    pub(crate) core_index: usize,
+    pub(crate) address: u64,
+    pub(crate) unit: WatchpointUnit,
+}
+
+impl WatchpointHandle {
+    /// Returns the index of the core whose accesses are watched.
+    pub fn core_index(&self) -> usize {
+        self.core_index
+    }
+
+    /// Returns the start address of the watched region.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+}
+
+/// The hardware unit used by a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum WatchpointUnit {
+    /// A comparator of the DWT of an ARM core.
+    Dwt(usize),
+    /// A trigger unit of a RISC-V core, which is found by the watched address.
+    Trigger,
+}